@@ -1,15 +1,134 @@
-use std::fmt::Display;
+use std::{fmt::Display, path::PathBuf, sync::OnceLock, time::Duration};
 
 use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::{ArgValueCandidates, CompletionCandidate, engine::ValueCandidates};
+
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  success
+  1  any other failure
+  2  invalid arguments
+  3  no compositor to connect to
+  4  the compositor lacks the required protocols
+  5  timed out waiting for the compositor
+  6  no toplevel, workspace, display or mark matches
+  7  a name or id matches more than one toplevel or workspace
+  8  the compositor does not allow the action
+  9  internal error, the compositor state is inconsistent
+
+With --format json errors are printed to stderr as
+{\"error\": {\"kind\": ..., \"message\": ..., \"exit_code\": ...}}";
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, after_long_help = EXIT_CODES)]
 pub struct Arguments {
     #[command(subcommand)]
     pub command: Command,
 
     #[arg(long, default_value_t)]
     pub format: OutputFormat,
+
+    /// how long to wait for the compositor, e.g. "500ms", "5s" or "1m"
+    #[arg(long, default_value = "5s", value_parser = parse_duration)]
+    pub timeout: Duration,
+
+    /// wait until the compositor applied the action and print the resulting state
+    ///
+    /// Fails if the change is not observed within the timeout.
+    #[arg(long)]
+    pub verify: bool,
+
+    /// print the requests that would be sent to the compositor instead of sending them
+    #[arg(long, conflicts_with = "verify")]
+    pub dry_run: bool,
+
+    /// run a list command against a file written by "snapshot" instead of the compositor
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["verify", "dry_run"])]
+    pub from_snapshot: Option<PathBuf>,
+}
+
+/// Parses durations like "250ms", "5s" or "2m". A plain number is read as seconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .map(|split| value.split_at(split))
+        .unwrap_or((value, "s"));
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration: {value}"))?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "s" => number,
+        "m" => number * 60.0,
+        unit => {
+            return Err(format!(
+                "unknown duration unit \"{unit}\", expected ms, s or m"
+            ));
+        }
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
+}
+
+/// What `pick` does with the selected toplevel.
+#[derive(Debug, Clone)]
+pub enum PickAction {
+    Activate,
+    Close,
+    Move(String),
+}
+
+/// Parses "activate", "close" or "move:<workspace>".
+pub fn parse_pick_action(value: &str) -> Result<PickAction, String> {
+    match value {
+        "activate" => Ok(PickAction::Activate),
+        "close" => Ok(PickAction::Close),
+        _ => match value.strip_prefix("move:") {
+            Some("") => Err("missing workspace, expected move:<workspace>".into()),
+            Some(workspace) => Ok(PickAction::Move(workspace.into())),
+            None => Err(format!(
+                "unknown action \"{value}\", expected activate, close or move:<workspace>"
+            )),
+        },
+    }
+}
+
+/// The live values an argument is completed with.
+///
+/// They come from the compositor, which this module knows nothing about, so
+/// the lookup is registered with [set_candidate_source]. Without it nothing
+/// is suggested.
+#[derive(Debug, Clone, Copy)]
+pub enum Candidates {
+    Toplevels,
+    Workspaces,
+    Displays,
+    /// toplevels and workspaces, for the target of `mark`
+    MarkTargets,
+}
+
+static CANDIDATE_SOURCE: OnceLock<fn(Candidates) -> Vec<CompletionCandidate>> = OnceLock::new();
+
+/// Set how [Candidates] are looked up, only the first call has an effect.
+pub fn set_candidate_source(source: fn(Candidates) -> Vec<CompletionCandidate>) {
+    let _ = CANDIDATE_SOURCE.set(source);
+}
+
+impl ValueCandidates for Candidates {
+    fn candidates(&self) -> Vec<CompletionCandidate> {
+        CANDIDATE_SOURCE
+            .get()
+            .map(|source| source(*self))
+            .unwrap_or_default()
+    }
+}
+
+/// A single command without the program name, as used by `batch`.
+#[derive(Parser, Debug)]
+#[command(no_binary_name = true)]
+pub struct CommandLine {
+    #[command(subcommand)]
+    pub command: Command,
 }
 
 #[derive(ValueEnum, Debug, Default, Clone, Copy)]
@@ -30,6 +149,42 @@ impl Display for OutputFormat {
     }
 }
 
+/// Status bars `status` can print for.
+#[derive(ValueEnum, Debug, Default, Clone, Copy)]
+pub enum Bar {
+    #[default]
+    Waybar,
+    I3blocks,
+}
+impl Display for Bar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Bar::Waybar => "waybar",
+            Bar::I3blocks => "i3blocks",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How `workspaces sort` orders the workspaces.
+#[derive(ValueEnum, Debug, Default, Clone, Copy)]
+pub enum SortKey {
+    /// by name, numbers first in numeric order
+    #[default]
+    Name,
+    /// the workspaces with the most toplevels first
+    ToplevelCount,
+}
+impl Display for SortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            SortKey::Name => "name",
+            SortKey::ToplevelCount => "toplevel-count",
+        };
+        f.write_str(name)
+    }
+}
+
 #[derive(Debug, Subcommand)]
 pub enum Command {
     /// List all windows with their properties.
@@ -56,6 +211,188 @@ pub enum Command {
         #[command(subcommand)]
         subcommand: Option<WorkspaceSubcommand>,
     },
+
+    /// Run multiple commands over a single connection.
+    ///
+    /// Reads one command per line, e.g. "toplevels max 1a2b" or
+    /// "workspaces pin 2 --display DP-1". Empty lines and lines starting
+    /// with '#' are ignored.
+    Batch {
+        /// file to read the commands from, "-" reads from stdin
+        #[arg(default_value = "-")]
+        file: String,
+
+        /// continue with the next command if a command fails
+        #[arg(short, long)]
+        keep_going: bool,
+    },
+
+    /// Move the focus to another toplevel.
+    ///
+    /// "last", "cycle" and "history" use the focus history recorded by
+    /// a running "conductor daemon".
+    Focus {
+        #[command(subcommand)]
+        subcommand: FocusSubcommand,
+    },
+
+    /// Select a toplevel with a launcher like fuzzel, rofi or dmenu.
+    ///
+    /// Every toplevel is passed to the launcher as "title — app_id [workspace]"
+    /// and the action is applied to the selected one.
+    Pick {
+        /// what to do with the selected toplevel: "activate", "close" or "move:<workspace>"
+        #[arg(short, long, default_value = "activate", value_parser = parse_pick_action)]
+        action: PickAction,
+
+        /// dmenu compatible launcher that reads the entries from stdin
+        #[arg(short, long, default_value = "fuzzel --dmenu")]
+        launcher: String,
+
+        /// run as rofi script mode instead of starting a launcher
+        ///
+        /// e.g. `rofi -show conductor -modes "conductor:conductor pick --rofi"`.
+        /// Can't be used with --format json.
+        #[arg(long, conflicts_with = "launcher")]
+        rofi: bool,
+
+        /// the selected entry, passed by rofi in script mode
+        #[arg(hide = true, requires = "rofi")]
+        selection: Option<String>,
+    },
+
+    /// Minimize all toplevels on the active workspaces or restore them.
+    ///
+    /// The minimized toplevels are remembered, so the next call restores
+    /// exactly those and focuses the toplevel that had focus before.
+    ShowDesktop {
+        /// only minimize the toplevels on the active workspace of this display
+        #[arg(short, long, add = ArgValueCandidates::new(Candidates::Displays))]
+        display: Option<String>,
+    },
+
+    /// Keep toplevels minimized and sticky and show them on demand, like the sway scratchpad.
+    Scratchpad {
+        #[command(subcommand)]
+        subcommand: ScratchpadSubcommand,
+    },
+
+    /// Name a toplevel or workspace, so it can be referred to as "@name".
+    ///
+    /// Marks work anywhere a toplevel id or workspace name is accepted and
+    /// last until logging out.
+    Mark {
+        /// the toplevel to mark, see "toplevels each" for the syntax
+        ///
+        /// the name of the workspace with --workspace
+        #[arg(add = ArgValueCandidates::new(Candidates::MarkTargets))]
+        target: String,
+
+        /// the name of the mark
+        name: String,
+
+        /// mark the workspace named target instead of a toplevel
+        #[arg(short, long)]
+        workspace: bool,
+
+        /// the display of the workspace if the name is not unique
+        #[arg(short, long, requires = "workspace", add = ArgValueCandidates::new(Candidates::Displays))]
+        display: Option<String>,
+    },
+
+    /// Undo the last commands.
+    ///
+    /// Restores the workspace, position, pin, maximize, minimize, fullscreen
+    /// and sticky state from before each command. Closing a toplevel can not
+    /// be undone.
+    Undo {
+        /// how many commands to undo
+        #[arg(default_value_t = 1)]
+        count: usize,
+    },
+
+    /// Print everything known about outputs, workspaces and toplevels as json.
+    ///
+    /// The file can be read back with "--from-snapshot", e.g. to reproduce a
+    /// bug report without the reporter's setup.
+    Snapshot,
+
+    /// Record the window, workspace and output changes the daemon reacts to.
+    ///
+    /// Writes the starting state followed by one json line per change with
+    /// the time since the recording started. Use "replay" to feed it through
+    /// the daemon again.
+    Record {
+        /// stop recording after this long, e.g. "30s", instead of when interrupted
+        #[arg(long, value_parser = parse_duration)]
+        duration: Option<Duration>,
+
+        /// file to write the recording to
+        file: PathBuf,
+    },
+
+    /// Feed a recording made with "record" through the daemon's event handling.
+    ///
+    /// Does not need a compositor. The hooks are run like in the daemon and
+    /// every event is printed with the hooks it started, followed by the
+    /// resulting focus history.
+    Replay {
+        /// file to read the recording from
+        file: PathBuf,
+
+        /// read the hooks from this file instead of the daemon's default, which has to exist
+        #[arg(long)]
+        hooks: Option<PathBuf>,
+    },
+
+    /// Start an interactive shell.
+    ///
+    /// Accepts the same commands as the command line and keeps the
+    /// connection to the compositor open between commands.
+    Shell,
+
+    /// Show a live dashboard of outputs, workspaces and toplevels.
+    ///
+    /// Select an item with the arrow keys and press enter to activate it,
+    /// "m" to minimize, "p" to pin, "v" to move or "c" to close it.
+    Tui,
+
+    /// Continuously print the active workspaces and focused window for a status bar.
+    ///
+    /// Prints a json line whenever the status changes. Use it with "exec" and
+    /// "return-type": "json" in a waybar custom module or with "interval=persist"
+    /// and "format=json" in i3blocks.
+    Status {
+        /// the bar to print the status for
+        #[arg(long, default_value_t)]
+        bar: Bar,
+    },
+
+    /// Watch the compositor, run hooks on window and workspace changes and record the focus history.
+    ///
+    /// Hooks are read from "$XDG_CONFIG_HOME/cosmic-conductor/hooks", one per
+    /// line in the form "on <event> [key=value ...] run <command>", e.g.
+    /// "on toplevel_opened app_id=zoom run notify-send 'Zoom started'".
+    Daemon {
+        /// read the hooks from this file instead, which has to exist
+        #[arg(long)]
+        hooks: Option<PathBuf>,
+
+        /// send a desktop notification when a window on a hidden workspace becomes urgent
+        ///
+        /// Clicking the notification activates the window. The compositor only
+        /// marks the workspace as urgent, not the window, so the notification names
+        /// the window on it that opened or changed last, e.g. by a title change.
+        /// That is not always the one that wants attention.
+        #[arg(long)]
+        notify_urgent: bool,
+    },
+
+    /// Check which protocols and capabilities the compositor supports.
+    ///
+    /// Lists all wayland globals the compositor advertises and reports
+    /// which conductor commands are available or degraded.
+    Doctor,
 }
 
 #[derive(Subcommand, Debug)]
@@ -66,11 +403,11 @@ pub enum ToplevelSubcommand {
         /// limit toplevels to workspace
         ///
         /// must be used with display if the name of the workspace is not unique
-        #[arg(short, long)]
+        #[arg(short, long, add = ArgValueCandidates::new(Candidates::Workspaces))]
         workspace: Option<String>,
 
         /// limit toplevels to display
-        #[arg(short, long)]
+        #[arg(short, long, add = ArgValueCandidates::new(Candidates::Displays))]
         display: Option<String>,
 
         /// show the geometry of each toplevel
@@ -83,6 +420,7 @@ pub enum ToplevelSubcommand {
         ///
         /// It is enough to provide the first characters as long as they
         /// are unique.
+        #[arg(add = ArgValueCandidates::new(Candidates::Toplevels))]
         id: String,
 
         /// undo maximize toplevel instead
@@ -103,6 +441,7 @@ pub enum ToplevelSubcommand {
         ///
         /// It is enough to provide the first characters as long as they
         /// are unique.
+        #[arg(add = ArgValueCandidates::new(Candidates::Toplevels))]
         id: String,
 
         /// undo minimize
@@ -124,6 +463,7 @@ pub enum ToplevelSubcommand {
         ///
         /// It is enough to provide the first characters as long as they
         /// are unique.
+        #[arg(add = ArgValueCandidates::new(Candidates::Toplevels))]
         id: String,
 
         /// fullscreen toplevel instead
@@ -144,6 +484,7 @@ pub enum ToplevelSubcommand {
         ///
         /// It is enough to provide the first characters as long as they
         /// are unique.
+        #[arg(add = ArgValueCandidates::new(Candidates::Toplevels))]
         id: String,
 
         /// unset sticky for toplevel instead
@@ -160,16 +501,105 @@ pub enum ToplevelSubcommand {
     },
 
     /// Activate the toplevel
-    Activate { id: String },
+    Activate {
+        #[arg(add = ArgValueCandidates::new(Candidates::Toplevels))]
+        id: String,
+    },
+
+    /// Move the toplevel to the active workspace of the focused display and activate it
+    Summon {
+        /// the unique id of the toplevel
+        ///
+        /// It is enough to provide the first characters as long as they
+        /// are unique.
+        #[arg(add = ArgValueCandidates::new(Candidates::Toplevels))]
+        id: String,
+    },
 
     /// Move toplevel to workspace
     Move {
+        #[arg(add = ArgValueCandidates::new(Candidates::Toplevels))]
         id: String,
 
+        #[arg(add = ArgValueCandidates::new(Candidates::Workspaces))]
         workspace: String,
 
         /// The display of the workspace if the name is not unique
+        #[arg(short, long, add = ArgValueCandidates::new(Candidates::Displays))]
+        display: Option<String>,
+    },
+
+    /// Move each toplevel to the workspace of the other one
+    Swap {
+        /// the unique id of the first toplevel
+        #[arg(add = ArgValueCandidates::new(Candidates::Toplevels))]
+        first: String,
+        /// the unique id of the second toplevel
+        #[arg(add = ArgValueCandidates::new(Candidates::Toplevels))]
+        second: String,
+    },
+
+    /// Apply an action to every toplevel matching a selector
+    ///
+    /// Prints whether the action succeeded for each toplevel.
+    Each {
+        /// which toplevels to act on
+        ///
+        /// Space separated key=value filters that all have to match, with
+        /// the keys id, title, app_id, workspace and output, e.g.
+        /// "app_id=foot workspace=3". A word without "=" is the start of an
+        /// id and "all" selects every toplevel. Marks like "@editor" can be
+        /// used for the id and the workspace.
+        #[arg(add = ArgValueCandidates::new(Candidates::Toplevels))]
+        selector: String,
+
+        #[command(subcommand)]
+        action: EachAction,
+    },
+}
+
+/// The actions `toplevels each` can apply.
+#[derive(Subcommand, Debug, Clone)]
+pub enum EachAction {
+    /// maximize the toplevels
+    Max {
+        /// undo maximize instead
+        #[arg(short, long, conflicts_with = "toggle")]
+        unset: bool,
+
+        /// toggle the maximize state of each toplevel
         #[arg(short, long)]
+        toggle: bool,
+    },
+    /// minimize the toplevels
+    Min {
+        /// undo minimize instead
+        #[arg(short, long, conflicts_with = "toggle")]
+        unset: bool,
+
+        /// toggle the minimize state of each toplevel
+        #[arg(short, long)]
+        toggle: bool,
+    },
+    /// mark the toplevels as sticky
+    Sticky {
+        /// unset sticky instead
+        #[arg(short, long, conflicts_with = "toggle")]
+        unset: bool,
+
+        /// toggle the sticky state of each toplevel
+        #[arg(short, long)]
+        toggle: bool,
+    },
+    /// close the toplevels
+    Close,
+    /// move the toplevels to a workspace
+    Move {
+        #[arg(add = ArgValueCandidates::new(Candidates::Workspaces))]
+        workspace: String,
+
+        /// The display of the workspace if the name is not unique
+        #[arg(short, long, add = ArgValueCandidates::new(Candidates::Displays))]
         display: Option<String>,
     },
 }
@@ -184,6 +614,59 @@ impl Default for ToplevelSubcommand {
     }
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum FocusSubcommand {
+    /// Activate the nearest toplevel left of the focused one on the same workspace
+    Left,
+    /// Activate the nearest toplevel right of the focused one on the same workspace
+    Right,
+    /// Activate the nearest toplevel above the focused one on the same workspace
+    Up,
+    /// Activate the nearest toplevel below the focused one on the same workspace
+    Down,
+    /// Activate a toplevel on the nearest display in the direction
+    Output { direction: Direction },
+    /// Activate the previously focused toplevel
+    Last,
+    /// Activate the toplevel that was focused the longest time ago
+    ///
+    /// Calling this repeatedly cycles through all toplevels.
+    Cycle {
+        /// only cycle through toplevels with this app id
+        #[arg(long)]
+        app_id: Option<String>,
+    },
+    /// List the toplevels from the most to the least recently focused
+    History,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ScratchpadSubcommand {
+    /// Minimize the matching toplevels and put them on the scratchpad
+    Send {
+        /// which toplevels to send, see "toplevels each" for the syntax
+        #[arg(add = ArgValueCandidates::new(Candidates::Toplevels))]
+        selector: String,
+
+        /// the name to show them by, defaults to the app id
+        #[arg(short, long)]
+        name: Option<String>,
+    },
+    /// Show a toplevel from the scratchpad on the current workspace or hide it again
+    Show {
+        /// only consider the toplevels sent with this name
+        name: Option<String>,
+    },
+}
+
 #[derive(Subcommand, Debug)]
 pub enum WorkspaceSubcommand {
     /// Move the workspace to the n-th position within it's group
@@ -202,6 +685,7 @@ pub enum WorkspaceSubcommand {
         workspace: WorkspaceIdent,
 
         /// the target display
+        #[arg(add = ArgValueCandidates::new(Candidates::Displays))]
         target_display: String,
         /// position on the display
         ///
@@ -224,6 +708,52 @@ pub enum WorkspaceSubcommand {
         workspace: WorkspaceIdent,
     },
 
+    /// Exchange the positions of two workspaces, even across displays
+    Swap {
+        /// the name of the first workspace
+        #[arg(add = ArgValueCandidates::new(Candidates::Workspaces))]
+        first: String,
+        /// the name of the second workspace
+        #[arg(add = ArgValueCandidates::new(Candidates::Workspaces))]
+        second: String,
+
+        /// the display of the first workspace if the name is not unique
+        #[arg(long, add = ArgValueCandidates::new(Candidates::Displays))]
+        first_display: Option<String>,
+
+        /// the display of the second workspace if the name is not unique
+        #[arg(long, add = ArgValueCandidates::new(Candidates::Displays))]
+        second_display: Option<String>,
+    },
+
+    /// Set the order of the workspaces on a display
+    ///
+    /// The workspaces are moved into the given order with as few moves as
+    /// possible. Workspaces that are not listed keep their order after the
+    /// listed ones.
+    Reorder {
+        /// the display of the workspaces
+        ///
+        /// this can be empty if there is only one display or the names are unique
+        #[arg(short, long, add = ArgValueCandidates::new(Candidates::Displays))]
+        display: Option<String>,
+
+        /// the names of the workspaces in the new order
+        #[arg(required = true, add = ArgValueCandidates::new(Candidates::Workspaces))]
+        workspaces: Vec<String>,
+    },
+
+    /// Sort the workspaces of each display
+    Sort {
+        /// only sort the workspaces of this display
+        #[arg(short, long, add = ArgValueCandidates::new(Candidates::Displays))]
+        display: Option<String>,
+
+        /// what to sort by
+        #[arg(long, default_value_t)]
+        by: SortKey,
+    },
+
     /// List all workspaces
     #[clap(alias = "l")]
     List {
@@ -241,13 +771,46 @@ impl Default for WorkspaceSubcommand {
     }
 }
 
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Clone)]
 pub struct WorkspaceIdent {
     /// the name of the workspace
+    #[arg(add = ArgValueCandidates::new(Candidates::Workspaces))]
     pub name: String,
     /// the display of the workspace
     ///
     /// this can be empty if the name is unique
-    #[arg(short, long)]
+    #[arg(short, long, add = ArgValueCandidates::new(Candidates::Displays))]
     pub display: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::error::ErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn dry_run_conflicts_with_verify() {
+        let err = Arguments::try_parse_from([
+            "conductor",
+            "--dry-run",
+            "--verify",
+            "toplevels",
+            "max",
+            "1a2b",
+        ])
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("5s"), Ok(Duration::from_secs(5)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1.5"), Ok(Duration::from_millis(1500)));
+        assert!(parse_duration("5h").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("-1s").is_err());
+    }
+}
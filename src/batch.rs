@@ -0,0 +1,91 @@
+use std::{
+    fs,
+    io::{Read, Write},
+};
+
+use anyhow::{Context, Result, bail};
+use clap::Parser;
+use log::debug;
+
+use crate::{
+    args::{Command, CommandLine},
+    command::{self, RunOptions},
+    cosmic::Session,
+    print::{Print, PrintList},
+};
+
+/// Run every command in `file` ("-" for stdin) against `session`.
+///
+/// Stops at the first failing command unless `keep_going` is set.
+pub fn run<W: Write>(
+    session: &mut Session,
+    file: &str,
+    keep_going: bool,
+    options: &RunOptions,
+    printer: &mut impl Print<W>,
+) -> Result<()> {
+    let script = if file == "-" {
+        let mut script = String::new();
+        std::io::stdin()
+            .read_to_string(&mut script)
+            .context("failed to read commands from stdin")?;
+        script
+    } else {
+        fs::read_to_string(file).with_context(|| format!("failed to read batch file {file}"))?
+    };
+
+    let mut failed = 0;
+    let mut total = 0;
+    let mut printer = printer.sub_list("Batch")?;
+    for (line_nr, line) in script.lines().enumerate().map(|(i, l)| (i + 1, l.trim())) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        total += 1;
+
+        let mut printer = printer.sub_struct()?;
+        printer.field("Line", line_nr)?;
+        printer.field("Command", line)?;
+
+        let result = parse(line).and_then(|command| {
+            session.refresh()?;
+            command::run(session, command, options, &mut printer)
+        });
+
+        match result {
+            Ok(()) => printer.field("Result", "ok")?,
+            Err(err) => {
+                failed += 1;
+                printer.field("Result", "error")?;
+                printer.field("Error", format!("{err:#}"))?;
+                if !keep_going {
+                    // keep the error itself, so the exit code is the one of the command
+                    return Err(err.context(format!("batch aborted after line {line_nr} failed")));
+                }
+            }
+        }
+    }
+    debug!("batch finished {total} commands, {failed} failed");
+
+    if failed > 0 {
+        bail!("{failed} of {total} batch commands failed");
+    }
+
+    Ok(())
+}
+
+fn parse(line: &str) -> Result<Command> {
+    let words = shlex::split(line).context("invalid quoting")?;
+    let command = CommandLine::try_parse_from(words)?.command;
+    match command {
+        Command::Batch { .. } => bail!("batch commands can not be nested"),
+        Command::Shell => bail!("the shell can not be started from a batch"),
+        Command::Tui => bail!("the tui can not be started from a batch"),
+        Command::Status { .. } => bail!("status can not be started from a batch"),
+        Command::Daemon { .. } => bail!("the daemon can not be started from a batch"),
+        Command::Snapshot => bail!("the snapshot can not be taken from a batch"),
+        Command::Record { .. } => bail!("recording can not be started from a batch"),
+        _ => {}
+    }
+    Ok(command)
+}
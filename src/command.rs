@@ -0,0 +1,276 @@
+use std::{io::Write, time::Duration};
+
+use anyhow::{Result, bail};
+
+use crate::{
+    args::{
+        Command, Direction, FocusSubcommand, ScratchpadSubcommand, ToplevelSubcommand,
+        WorkspaceIdent, WorkspaceSubcommand,
+    },
+    batch,
+    cosmic::Session,
+    desktop, doctor, focus, marks, output, pick,
+    print::Print,
+    record, scratchpad, selector,
+    snapshot::Snapshot,
+    toplevel::{self, SetStateAction},
+    undo, verify, workspace,
+};
+
+/// Options that apply to every command.
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    pub timeout: Duration,
+    pub verify: bool,
+    /// print the requests instead of sending them
+    pub dry_run: bool,
+}
+
+/// Run a read-only command against a snapshot instead of the compositor.
+pub fn run_from_snapshot<W: Write>(
+    snapshot: &Snapshot,
+    command: Command,
+    printer: &mut impl Print<W>,
+) -> Result<()> {
+    match command {
+        Command::Toplevels { subcommand } => match subcommand.unwrap_or_default() {
+            ToplevelSubcommand::List {
+                display,
+                workspace,
+                geometry,
+            } => toplevel::list(snapshot, printer, workspace, display, geometry),
+            _ => bail!("only \"toplevels list\" can be run from a snapshot"),
+        },
+        Command::Outputs => output::list(snapshot, printer),
+        Command::WorkspaceGroups => workspace::list_groups(snapshot, printer),
+        Command::Workspaces { subcommand } => match subcommand.unwrap_or_default() {
+            WorkspaceSubcommand::List { capabilities } => {
+                workspace::list(snapshot, printer, capabilities)
+            }
+            _ => bail!("only \"workspaces list\" can be run from a snapshot"),
+        },
+        _ => bail!("this command needs a running compositor and can not be run from a snapshot"),
+    }
+}
+
+/// Run a single command against an established session.
+pub fn run<W: Write>(
+    session: &mut Session,
+    command: Command,
+    options: &RunOptions,
+    printer: &mut impl Print<W>,
+) -> Result<()> {
+    session.app_data.requests.borrow_mut().clear();
+    session.app_data.dry_run = options.dry_run;
+    *session.app_data.inverses.borrow_mut() = Some(Vec::new());
+    let app_data = &session.app_data;
+    // `toplevels each` reports failures per toplevel but still sends the other requests
+    let mut failed = 0;
+
+    match command {
+        Command::Toplevels { subcommand } => match subcommand.unwrap_or_default() {
+            ToplevelSubcommand::List {
+                display,
+                workspace,
+                geometry,
+            } => toplevel::list(
+                &Snapshot::new(app_data),
+                printer,
+                workspace,
+                display,
+                geometry,
+            )?,
+            ToplevelSubcommand::Activate { id } => {
+                toplevel::activate(app_data, toplevel::find_from_id(app_data, &id)?)?
+            }
+            ToplevelSubcommand::Summon { id } => {
+                toplevel::summon(app_data, toplevel::find_from_id(app_data, &id)?)?
+            }
+            ToplevelSubcommand::Max {
+                id,
+                unset: minimize,
+                toggle,
+            } => toplevel::maximize(
+                app_data,
+                toplevel::find_from_id(app_data, &id)?,
+                SetStateAction::from(minimize, toggle)?,
+            )?,
+            ToplevelSubcommand::Min {
+                id,
+                unset: minimize,
+                toggle,
+            } => toplevel::minimize(
+                app_data,
+                toplevel::find_from_id(app_data, &id)?,
+                SetStateAction::from(minimize, toggle)?,
+            )?,
+            ToplevelSubcommand::Fullscreen {
+                id,
+                minimize,
+                toggle,
+            } => toplevel::fullscreen(
+                app_data,
+                toplevel::find_from_id(app_data, &id)?,
+                SetStateAction::from(minimize, toggle)?,
+            )?,
+            ToplevelSubcommand::Sticky {
+                id,
+                minimize,
+                toggle,
+            } => toplevel::sticky(
+                app_data,
+                toplevel::find_from_id(app_data, &id)?,
+                SetStateAction::from(minimize, toggle)?,
+            )?,
+            ToplevelSubcommand::Move {
+                id,
+                workspace,
+                display,
+            } => toplevel::move_to(
+                app_data,
+                toplevel::find_from_id(app_data, &id)?,
+                &WorkspaceIdent {
+                    name: workspace,
+                    display,
+                },
+            )?,
+            ToplevelSubcommand::Swap { first, second } => toplevel::swap(
+                app_data,
+                toplevel::find_from_id(app_data, &first)?,
+                toplevel::find_from_id(app_data, &second)?,
+            )?,
+            ToplevelSubcommand::Each { selector, action } => {
+                failed = selector::each(app_data, printer, &selector, &action)?
+            }
+        },
+        Command::Outputs => output::list(&Snapshot::new(app_data), printer)?,
+        Command::WorkspaceGroups => workspace::list_groups(&Snapshot::new(app_data), printer)?,
+        Command::Workspaces { subcommand } => match subcommand.unwrap_or_default() {
+            WorkspaceSubcommand::List { capabilities } => {
+                workspace::list(&Snapshot::new(app_data), printer, capabilities)?
+            }
+            WorkspaceSubcommand::MoveToPos {
+                workspace,
+                position,
+            } => workspace::move_to(app_data, workspace, position.into(), None)?,
+            WorkspaceSubcommand::MoveToDisplay {
+                workspace,
+                target_display,
+                position,
+            } => workspace::move_to(
+                app_data,
+                workspace,
+                position.map(Into::into).unwrap_or(usize::MAX),
+                Some(&target_display),
+            )?,
+            WorkspaceSubcommand::Pin { workspace } => workspace::pin(app_data, workspace, true)?,
+            WorkspaceSubcommand::Unpin { workspace } => workspace::pin(app_data, workspace, false)?,
+            WorkspaceSubcommand::Activate { workspace } => {
+                workspace::activate(app_data, workspace)?
+            }
+            WorkspaceSubcommand::Reorder {
+                display,
+                workspaces,
+            } => workspace::reorder(app_data, display.as_deref(), &workspaces)?,
+            WorkspaceSubcommand::Sort { display, by } => {
+                workspace::sort(app_data, display.as_deref(), by)?
+            }
+            WorkspaceSubcommand::Swap {
+                first,
+                second,
+                first_display,
+                second_display,
+            } => workspace::swap(
+                app_data,
+                WorkspaceIdent {
+                    name: first,
+                    display: first_display,
+                },
+                WorkspaceIdent {
+                    name: second,
+                    display: second_display,
+                },
+            )?,
+        },
+        Command::Focus { subcommand } => match subcommand {
+            FocusSubcommand::Left => focus::direction(app_data, Direction::Left)?,
+            FocusSubcommand::Right => focus::direction(app_data, Direction::Right)?,
+            FocusSubcommand::Up => focus::direction(app_data, Direction::Up)?,
+            FocusSubcommand::Down => focus::direction(app_data, Direction::Down)?,
+            FocusSubcommand::Output { direction } => focus::output(app_data, direction)?,
+            FocusSubcommand::Last => focus::last(app_data)?,
+            FocusSubcommand::Cycle { app_id } => focus::cycle(app_data, app_id.as_deref())?,
+            FocusSubcommand::History => focus::print_history(app_data, printer)?,
+        },
+        Command::Pick {
+            action,
+            launcher,
+            rofi,
+            ..
+        } => {
+            if rofi {
+                pick::rofi(app_data, &action)?
+            } else {
+                pick::run(app_data, &launcher, &action)?
+            }
+        }
+        Command::Scratchpad { subcommand } => match subcommand {
+            ScratchpadSubcommand::Send { selector, name } => {
+                scratchpad::send(app_data, &selector, name.as_deref())?
+            }
+            ScratchpadSubcommand::Show { name } => scratchpad::show(app_data, name.as_deref())?,
+        },
+        Command::Mark {
+            target,
+            name,
+            workspace: false,
+            ..
+        } => marks::mark_toplevel(app_data, &target, &name)?,
+        Command::Mark {
+            target,
+            name,
+            workspace: true,
+            display,
+        } => marks::mark_workspace(
+            app_data,
+            &WorkspaceIdent {
+                name: target,
+                display,
+            },
+            &name,
+        )?,
+        Command::ShowDesktop { display } => desktop::show(app_data, display.as_deref())?,
+        Command::Doctor => doctor::report(printer, options.timeout)?,
+        Command::Batch { file, keep_going } => {
+            return batch::run(session, &file, keep_going, options, printer);
+        }
+        Command::Undo { count } => {
+            return undo::run(session, count, options.timeout, printer);
+        }
+        Command::Shell => bail!("the shell can only be started from the command line"),
+        Command::Tui => bail!("the tui can only be started from the command line"),
+        Command::Status { .. } => bail!("status can only be started from the command line"),
+        Command::Daemon { .. } => bail!("the daemon can only be started from the command line"),
+        Command::Snapshot => bail!("the snapshot can only be taken from the command line"),
+        Command::Record { .. } => bail!("recording can only be started from the command line"),
+        Command::Replay { file, hooks } => return record::replay(&file, hooks, printer),
+    }
+    if options.dry_run {
+        let requests = session.app_data.requests.borrow();
+        printer.list("Requests", requests.iter())?;
+    } else {
+        session.event_queue.flush()?;
+        undo::record(&session.app_data)?;
+    }
+
+    // nothing was sent in a dry run, so there is nothing the compositor could apply
+    if options.verify && !options.dry_run {
+        verify::wait_until_applied(session, options.timeout)?;
+        verify::print_state(&session.app_data, printer)?;
+    }
+    if failed > 0 {
+        bail!("the action failed for {failed} toplevels");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use clap::CommandFactory;
+use clap_complete::CompletionCandidate;
+use itertools::Itertools;
+
+use crate::{
+    args::{self, Arguments, Candidates},
+    cosmic::{AppData, Session},
+    output, workspace,
+};
+
+/// How long a shell completion waits for the compositor.
+const COMPLETION_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The command line definition with dynamic completions for toplevel ids,
+/// workspace names and displays.
+pub fn command() -> clap::Command {
+    args::set_candidate_source(live_candidates);
+    Arguments::command()
+}
+
+/// Connect to the compositor and collect candidates.
+///
+/// Completions must never fail loudly, so any error results in no candidates.
+fn live_candidates(candidates: Candidates) -> Vec<CompletionCandidate> {
+    let Ok(session) = Session::connect(COMPLETION_TIMEOUT) else {
+        return Vec::new();
+    };
+    let app_data = &session.app_data;
+    let candidates = match candidates {
+        Candidates::Toplevels => toplevels(app_data),
+        Candidates::Workspaces => workspaces(app_data),
+        Candidates::Displays => displays(app_data),
+        Candidates::MarkTargets => [toplevels(app_data), workspaces(app_data)].concat(),
+    };
+    candidates
+        .into_iter()
+        .map(|c| CompletionCandidate::new(c.value).help(c.help.map(Into::into)))
+        .collect()
+}
+
+/// A value that can be suggested when completing an argument.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub value: String,
+    pub help: Option<String>,
+}
+
+/// Toplevel identifiers, annotated with the toplevel title.
+pub fn toplevels(app_data: &AppData) -> Vec<Candidate> {
+    app_data
+        .toplevel_info_state
+        .toplevels()
+        .map(|t| Candidate {
+            value: t.identifier.clone(),
+            help: Some(format!("{} ({})", t.title, t.app_id)),
+        })
+        .collect()
+}
+
+/// Workspace names, annotated with the displays they are on.
+pub fn workspaces(app_data: &AppData) -> Vec<Candidate> {
+    app_data
+        .workspace_state
+        .workspaces()
+        .map(|w| Candidate {
+            value: w.name.clone(),
+            help: Some(
+                workspace::get_groups_for_workspace(w, app_data)
+                    .flat_map(|g| g.outputs.iter())
+                    .filter_map(|o| app_data.output_state.info(o))
+                    .map(|o| output::display_name(&o))
+                    .join(", "),
+            ),
+        })
+        .unique_by(|c| c.value.clone())
+        .collect()
+}
+
+/// Display names, annotated with the display description.
+pub fn displays(app_data: &AppData) -> Vec<Candidate> {
+    app_data
+        .output_state
+        .outputs()
+        .filter_map(|o| app_data.output_state.info(&o))
+        .map(|o| Candidate {
+            value: output::display_name(&o),
+            help: o.description.clone(),
+        })
+        .collect()
+}
@@ -1,3 +1,4 @@
+use anyhow::bail;
 use cosmic_client_toolkit::{
     sctk::{
         self,
@@ -9,10 +10,211 @@ use cosmic_client_toolkit::{
     toplevel_management::{ToplevelManagerHandler, ToplevelManagerState},
     workspace::{WorkspaceHandler, WorkspaceState},
 };
-use log::trace;
-use wayland_client::QueueHandle;
+use cosmic_protocols::toplevel_management::v1::client::zcosmic_toplevel_manager_v1::ZcosmicToplelevelManagementCapabilitiesV1;
+use log::{debug, trace};
+use rustix::{
+    event::{PollFd, PollFlags, Timespec, poll},
+    io::Errno,
+};
+use wayland_client::{
+    Connection, EventQueue, QueueHandle, WEnum,
+    backend::WaylandError,
+    globals::{GlobalList, registry_queue_init},
+};
 use wayland_protocols::ext::foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1;
 
+use crate::{error::Error, event::Change, request::Request, undo::Inverse};
+
+use std::{
+    cell::RefCell,
+    cmp::min,
+    io, iter,
+    os::fd::BorrowedFd,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Wayland globals conductor can not work without.
+pub const REQUIRED_GLOBALS: &[&str] = &[
+    "ext_foreign_toplevel_list_v1",
+    "ext_workspace_manager_v1",
+    "zcosmic_toplevel_info_v1",
+    "zcosmic_toplevel_manager_v1",
+    "zcosmic_workspace_manager_v2",
+];
+
+/// Connect to the compositor and receive the list of advertised globals.
+pub fn connect_globals() -> anyhow::Result<(GlobalList, EventQueue<AppData>)> {
+    let connection = Connection::connect_to_env().map_err(|err| {
+        Error::NoCompositor(format!(
+            "Could not connect to the wayland compositor: {err}"
+        ))
+    })?;
+    Ok(registry_queue_init(&connection)?)
+}
+
+/// Returns the [REQUIRED_GLOBALS] the compositor does not advertise.
+pub fn missing_globals(globals: &GlobalList) -> Vec<&'static str> {
+    globals.contents().with_list(|list| {
+        let advertised: Vec<_> = list.iter().map(|g| g.interface.as_str()).collect();
+        missing_from(&advertised)
+    })
+}
+
+/// Returns the [REQUIRED_GLOBALS] that are not in `advertised`.
+fn missing_from(advertised: &[&str]) -> Vec<&'static str> {
+    REQUIRED_GLOBALS
+        .iter()
+        .copied()
+        .filter(|required| !advertised.contains(required))
+        .collect()
+}
+
+/// An open connection to the compositor together with the received state.
+pub struct Session {
+    pub event_queue: EventQueue<AppData>,
+    pub app_data: AppData,
+}
+
+impl Session {
+    /// Connect to the compositor and wait until the initial state is received.
+    ///
+    /// Fails with [Error::NoCompositor], [Error::ProtocolUnavailable] or
+    /// [Error::Timeout] if the connection can't be established, required
+    /// globals are missing or the initial state does not arrive within `timeout`.
+    pub fn connect(timeout: Duration) -> anyhow::Result<Self> {
+        let (globals, event_queue) = connect_globals()?;
+
+        let missing = missing_globals(&globals);
+        if !missing.is_empty() {
+            bail!(Error::ProtocolUnavailable(format!(
+                "The compositor does not support the required protocols: {}. Is this a COSMIC session?",
+                missing.join(", ")
+            )));
+        }
+
+        Self::from_globals(&globals, event_queue, timeout)
+    }
+
+    /// Bind all globals and wait until the initial state is received.
+    ///
+    /// The caller is responsible for checking [missing_globals] first.
+    pub fn from_globals(
+        globals: &GlobalList,
+        event_queue: EventQueue<AppData>,
+        timeout: Duration,
+    ) -> anyhow::Result<Self> {
+        let qh = event_queue.handle();
+        let registry_state = RegistryState::new(globals);
+
+        let app_data = AppData {
+            output_state: OutputState::new(globals, &qh),
+            seat_state: SeatState::new(globals, &qh),
+            workspace_state: WorkspaceState::new(&registry_state, &qh),
+            toplevel_info_state: ToplevelInfoState::new(&registry_state, &qh),
+            toplevel_manager_state: ToplevelManagerState::new(&registry_state, &qh),
+            registry_state,
+            toplevel_capabilities: Vec::new(),
+            requests: RefCell::new(Vec::new()),
+            dry_run: false,
+            inverses: RefCell::new(None),
+            changes: None,
+            toplevl_done: false,
+            workspace_done: false,
+            output_count: 0,
+        };
+
+        let mut session = Session {
+            event_queue,
+            app_data,
+        };
+        session.wait_for_initial_state(timeout)?;
+
+        Ok(session)
+    }
+
+    /// Process all pending events so the state reflects the compositor.
+    pub fn refresh(&mut self) -> anyhow::Result<()> {
+        self.event_queue.roundtrip(&mut self.app_data)?;
+        Ok(())
+    }
+
+    /// Dispatch the events the compositor sent so far without blocking.
+    ///
+    /// Returns the number of dispatched events.
+    pub fn dispatch_pending(&mut self) -> anyhow::Result<usize> {
+        self.event_queue.flush()?;
+        if let Some(guard) = self.event_queue.prepare_read() {
+            match guard.read() {
+                Ok(_) => {}
+                Err(WaylandError::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(self.event_queue.dispatch_pending(&mut self.app_data)?)
+    }
+
+    /// Wait up to `timeout` for events from the compositor and dispatch them.
+    ///
+    /// Returns early if one of the `wake` file descriptors becomes readable.
+    /// Returns the number of dispatched events.
+    pub fn dispatch_timeout(
+        &mut self,
+        timeout: Duration,
+        wake: &[BorrowedFd<'_>],
+    ) -> anyhow::Result<usize> {
+        self.event_queue.flush()?;
+        let Some(guard) = self.event_queue.prepare_read() else {
+            // events are already queued
+            return Ok(self.event_queue.dispatch_pending(&mut self.app_data)?);
+        };
+
+        let fd = guard.connection_fd();
+        let mut fds: Vec<_> = iter::once(&fd)
+            .chain(wake)
+            .map(|fd| PollFd::new(fd, PollFlags::IN))
+            .collect();
+        let timeout = Timespec::try_from(timeout)?;
+        match poll(&mut fds, Some(&timeout)) {
+            Ok(_) | Err(Errno::INTR) => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        if fds[0].revents().contains(PollFlags::IN) {
+            match guard.read() {
+                Ok(_) => {}
+                Err(WaylandError::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(self.event_queue.dispatch_pending(&mut self.app_data)?)
+    }
+
+    fn wait_for_initial_state(&mut self, timeout: Duration) -> anyhow::Result<()> {
+        let start = Instant::now();
+        let mut count = 1u64;
+        let mut delay = Duration::from_millis(20);
+        while !self.app_data.initial_state_done() {
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                bail!(Error::Timeout(format!(
+                    "Timed out after {timeout:?} waiting for the compositor to send: {}",
+                    self.app_data.missing_initial_state().join(", ")
+                )));
+            }
+            if self.event_queue.roundtrip(&mut self.app_data)? == 0 {
+                let delay_sleep = min(delay, timeout - elapsed);
+                thread::sleep(delay_sleep);
+                trace!("roundtrip sleep: {:?}", delay_sleep);
+                delay = min(delay * 2, Duration::from_millis(200));
+            }
+            count += 1;
+        }
+        debug!("finished {count} wayland event roundtrips");
+        Ok(())
+    }
+}
+
 pub struct AppData {
     pub output_state: OutputState,
     pub registry_state: RegistryState,
@@ -21,11 +223,51 @@ pub struct AppData {
     pub toplevel_manager_state: ToplevelManagerState,
     pub seat_state: SeatState,
 
+    pub toplevel_capabilities: Vec<WEnum<ZcosmicToplelevelManagementCapabilitiesV1>>,
+
+    /// every request sent through [AppData::send]
+    pub requests: RefCell<Vec<Request>>,
+
+    /// only record requests instead of sending them
+    pub dry_run: bool,
+
+    /// what undoes the requests sent so far, only recorded if this is `Some`
+    pub inverses: RefCell<Option<Vec<Inverse>>>,
+
+    /// changes reported by the handlers, only recorded if this is `Some`
+    pub changes: Option<Vec<Change>>,
+
     pub toplevl_done: bool,
     pub workspace_done: bool,
     pub output_count: u32,
 }
 
+impl AppData {
+    pub fn initial_state_done(&self) -> bool {
+        self.toplevl_done && self.output_count > 0 && self.workspace_done
+    }
+
+    fn record(&mut self, change: Change) {
+        if let Some(changes) = self.changes.as_mut() {
+            changes.push(change);
+        }
+    }
+
+    fn missing_initial_state(&self) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        if !self.toplevl_done {
+            missing.push("toplevel info");
+        }
+        if !self.workspace_done {
+            missing.push("workspace info");
+        }
+        if self.output_count == 0 {
+            missing.push("outputs (no display connected, is this a headless session?)");
+        }
+        missing
+    }
+}
+
 sctk::delegate_output!(AppData);
 sctk::delegate_registry!(AppData);
 sctk::delegate_seat!(AppData);
@@ -54,6 +296,7 @@ impl OutputHandler for AppData {
     ) {
         self.output_count += 1;
         trace!("new output: {output:?}");
+        self.record(Change::OutputAdded(output));
     }
 
     fn update_output(
@@ -63,6 +306,7 @@ impl OutputHandler for AppData {
         output: wayland_client::protocol::wl_output::WlOutput,
     ) {
         trace!("update output: {output:?}");
+        self.record(Change::OutputUpdated(output));
     }
 
     fn output_destroyed(
@@ -73,6 +317,7 @@ impl OutputHandler for AppData {
     ) {
         self.output_count -= 1;
         trace!("destroy output: {output:?}");
+        self.record(Change::OutputRemoved(output));
     }
 }
 
@@ -84,6 +329,7 @@ impl WorkspaceHandler for AppData {
     fn done(&mut self) {
         trace!("workspace info done");
         self.workspace_done = true;
+        self.record(Change::WorkspacesDone);
     }
 }
 
@@ -99,6 +345,7 @@ impl ToplevelInfoHandler for AppData {
         toplevel: &ExtForeignToplevelHandleV1,
     ) {
         trace!("new toplevel: {toplevel:?}");
+        self.record(Change::ToplevelOpened(toplevel.clone()));
     }
 
     fn update_toplevel(
@@ -108,6 +355,7 @@ impl ToplevelInfoHandler for AppData {
         toplevel: &ExtForeignToplevelHandleV1,
     ) {
         trace!("update toplevel: {toplevel:?}");
+        self.record(Change::ToplevelUpdated(toplevel.clone()));
     }
 
     fn toplevel_closed(
@@ -117,6 +365,7 @@ impl ToplevelInfoHandler for AppData {
         toplevel: &ExtForeignToplevelHandleV1,
     ) {
         trace!("closed toplevel: {toplevel:?}");
+        self.record(Change::ToplevelClosed(toplevel.clone()));
     }
 
     fn info_done(&mut self, _conn: &wayland_client::Connection, _qh: &QueueHandle<Self>) {
@@ -134,11 +383,10 @@ impl ToplevelManagerHandler for AppData {
         &mut self,
         _conn: &wayland_client::Connection,
         _qh: &QueueHandle<Self>,
-        capabilities: Vec<
-            wayland_client::WEnum<cosmic_protocols::toplevel_management::v1::client::zcosmic_toplevel_manager_v1::ZcosmicToplelevelManagementCapabilitiesV1>,
-        >,
+        capabilities: Vec<WEnum<ZcosmicToplelevelManagementCapabilitiesV1>>,
     ) {
         trace!("toplevel manager cap: {capabilities:?}");
+        self.toplevel_capabilities = capabilities;
     }
 }
 
@@ -185,3 +433,22 @@ impl SeatHandler for AppData {
         trace!("seat removed: {seat:?}");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_only_missing_globals() {
+        assert_eq!(missing_from(REQUIRED_GLOBALS), Vec::<&str>::new());
+        assert_eq!(
+            missing_from(&["wl_seat", "ext_workspace_manager_v1"]),
+            [
+                "ext_foreign_toplevel_list_v1",
+                "zcosmic_toplevel_info_v1",
+                "zcosmic_toplevel_manager_v1",
+                "zcosmic_workspace_manager_v2",
+            ]
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use std::{collections::HashMap, process::Child, time::Duration};
+
+use anyhow::{Result, bail};
+use itertools::Itertools;
+use log::{debug, warn};
+
+use crate::{
+    cosmic::{AppData, Session},
+    event::{Event, ToplevelSnapshot, Tracker, WorkspaceSnapshot},
+    focus::{self, History},
+    hooks::Hook,
+    ipc::Server,
+    marks,
+    notify::Notifier,
+    scratchpad, toplevel,
+};
+
+/// How often the daemon checks for clicked notifications while waiting for events.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+pub struct Options {
+    pub hooks: Vec<Hook>,
+    /// notify about urgent windows on workspaces that are not visible
+    pub notify_urgent: bool,
+}
+
+/// Watch the compositor and react to every event as configured in `options`.
+///
+/// Also records the focus history and serves it to `focus` commands over [Server].
+///
+/// Runs until the connection to the compositor is lost.
+pub fn run(session: &mut Session, options: &Options) -> Result<()> {
+    debug!("daemon started with {} hooks", options.hooks.len());
+    let mut tracker = Tracker::new(&mut session.app_data);
+    let mut notifier = if options.notify_urgent {
+        Some(Notifier::connect()?)
+    } else {
+        None
+    };
+    let server = Server::bind()?;
+    let mut handler = Handler::new(&options.hooks, History::new(&session.app_data));
+
+    loop {
+        session.dispatch_timeout(POLL_INTERVAL, &[server.fd()])?;
+
+        for event in tracker.process(&mut session.app_data) {
+            handler.handle(&event);
+
+            match &event {
+                Event::ToplevelClosed(toplevel) => {
+                    if let Err(err) = scratchpad::forget(&session.app_data, &toplevel.identifier) {
+                        warn!("failed to remove closed toplevel from the scratchpad: {err:#}");
+                    }
+                    if let Err(err) =
+                        marks::forget_toplevel(&session.app_data, &toplevel.identifier)
+                    {
+                        warn!("failed to remove the marks of closed toplevel: {err:#}");
+                    }
+                }
+                Event::WorkspaceUrgent(workspace) if !workspace.active => {
+                    if let Some(notifier) = notifier.as_mut() {
+                        let last_changed = handler.last_changed(workspace);
+                        notify_urgent(notifier, &session.app_data, workspace, last_changed);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        server.accept(|request| match request {
+            focus::HISTORY_REQUEST => Ok(handler.history().identifiers().to_vec()),
+            _ => bail!("unknown request \"{request}\""),
+        });
+
+        if let Some(notifier) = notifier.as_mut() {
+            for id in notifier.clicked() {
+                debug!("notification for toplevel {id} clicked");
+                let result = toplevel::find_from_id(&session.app_data, &id)
+                    .and_then(|toplevel| toplevel::activate(&session.app_data, toplevel));
+                if let Err(err) = result {
+                    warn!("failed to activate toplevel {id}: {err:#}");
+                }
+            }
+        }
+
+        handler.reap();
+    }
+}
+
+/// What the daemon does with every event that doesn't need the compositor.
+///
+/// `replay` uses it as well, so a recording is handled like live events.
+pub struct Handler<'a> {
+    hooks: &'a [Hook],
+    history: History,
+    running: Vec<Child>,
+    /// the last toplevel that changed on each workspace by name and display, likely
+    /// the one that wants attention
+    last_changed: HashMap<(String, String), ToplevelSnapshot>,
+}
+
+impl<'a> Handler<'a> {
+    pub fn new(hooks: &'a [Hook], history: History) -> Self {
+        Self {
+            hooks,
+            history,
+            running: Vec::new(),
+            last_changed: HashMap::new(),
+        }
+    }
+
+    /// Record `event` in the focus history and start the hooks matching it.
+    ///
+    /// Returns the hooks that were started.
+    pub fn handle(&mut self, event: &Event) -> Vec<&'a Hook> {
+        debug!("event: {event}");
+        self.history.record(event);
+
+        let mut started = Vec::new();
+        for hook in self.hooks.iter().filter(|hook| hook.matches(event)) {
+            match hook.spawn(event) {
+                Ok(child) => {
+                    self.running.push(child);
+                    started.push(hook);
+                }
+                Err(err) => warn!("failed to run hook \"{}\": {err:#}", hook.command),
+            }
+        }
+
+        if let Event::ToplevelOpened(toplevel) | Event::ToplevelChanged(toplevel) = event {
+            // workspace names repeat on every display
+            for (workspace, output) in toplevel
+                .workspaces
+                .iter()
+                .cartesian_product(&toplevel.outputs)
+            {
+                self.last_changed
+                    .insert((workspace.clone(), output.clone()), toplevel.clone());
+            }
+        }
+        started
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// The toplevel on `workspace` that was opened or changed last.
+    pub fn last_changed(&self, workspace: &WorkspaceSnapshot) -> Option<&ToplevelSnapshot> {
+        workspace.outputs.iter().find_map(|output| {
+            self.last_changed
+                .get(&(workspace.name.clone(), output.clone()))
+        })
+    }
+
+    /// Reap finished hooks so they don't linger as zombies.
+    pub fn reap(&mut self) {
+        self.running.retain_mut(|child| match child.try_wait() {
+            Ok(Some(status)) => {
+                if !status.success() {
+                    warn!("hook {} exited with {status}", child.id());
+                }
+                false
+            }
+            Ok(None) => true,
+            Err(err) => {
+                warn!("failed to wait for hook {}: {err}", child.id());
+                false
+            }
+        });
+    }
+
+    /// Wait until all started hooks finished.
+    pub fn wait(&mut self) {
+        for mut child in self.running.drain(..) {
+            match child.wait() {
+                Ok(status) if !status.success() => {
+                    warn!("hook {} exited with {status}", child.id())
+                }
+                Ok(_) => {}
+                Err(err) => warn!("failed to wait for hook {}: {err}", child.id()),
+            }
+        }
+    }
+}
+
+/// The protocols only mark the workspace as urgent, so the notification is for
+/// the toplevel on it that changed last.
+fn notify_urgent(
+    notifier: &mut Notifier,
+    app_data: &AppData,
+    workspace: &WorkspaceSnapshot,
+    last_changed: Option<&ToplevelSnapshot>,
+) {
+    let toplevel = last_changed.cloned().or_else(|| {
+        app_data
+            .toplevel_info_state
+            .toplevels()
+            .map(|t| ToplevelSnapshot::new(app_data, t))
+            .find(|t| {
+                t.workspaces.contains(&workspace.name)
+                    && t.outputs.iter().any(|o| workspace.outputs.contains(o))
+            })
+    });
+    let Some(toplevel) = toplevel else {
+        debug!("no toplevel on urgent workspace {}", workspace.name);
+        return;
+    };
+    if let Err(err) = notifier.urgent(workspace, &toplevel) {
+        warn!("failed to send notification: {err:#}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toplevel(identifier: &str, output: &str) -> ToplevelSnapshot {
+        ToplevelSnapshot {
+            identifier: identifier.to_string(),
+            title: String::new(),
+            app_id: String::new(),
+            workspaces: vec!["1".to_string()],
+            outputs: vec![output.to_string()],
+            activated: false,
+        }
+    }
+
+    fn workspace(output: &str) -> WorkspaceSnapshot {
+        WorkspaceSnapshot {
+            name: "1".to_string(),
+            outputs: vec![output.to_string()],
+            active: false,
+            urgent: true,
+        }
+    }
+
+    #[test]
+    fn last_changed_on_the_workspace_of_each_display() {
+        let mut handler = Handler::new(&[], History::default());
+        handler.handle(&Event::ToplevelOpened(toplevel("a1", "DP-1")));
+        handler.handle(&Event::ToplevelChanged(toplevel("b2", "DP-2")));
+
+        let last_changed = |output| {
+            handler
+                .last_changed(&workspace(output))
+                .map(|t| t.identifier.clone())
+        };
+        assert_eq!(last_changed("DP-1").as_deref(), Some("a1"));
+        assert_eq!(last_changed("DP-2").as_deref(), Some("b2"));
+        assert_eq!(last_changed("HDMI-A-1"), None);
+    }
+}
@@ -0,0 +1,119 @@
+use anyhow::{Result, bail};
+use cosmic_protocols::toplevel_info::v1::client::zcosmic_toplevel_handle_v1::State;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use wayland_protocols::ext::workspace::v1::client::ext_workspace_handle_v1::State as WorkspaceState;
+
+use crate::{
+    cosmic::AppData,
+    error::Error,
+    output, state,
+    toplevel::{self, SetStateAction},
+    workspace,
+};
+
+/// What the last `show-desktop` minimized.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Shown {
+    minimized: Vec<String>,
+    focused: Option<String>,
+}
+
+/// Each display has its own state, so the desktop can be shown on one
+/// display while it is restored on another.
+fn state_name(display: Option<&str>) -> String {
+    match display {
+        Some(display) => format!("show-desktop@{display}"),
+        None => "show-desktop".to_string(),
+    }
+}
+
+/// Restore the toplevels the last call minimized or minimize all toplevels on
+/// the active workspaces if there is nothing to restore.
+pub fn show(app_data: &AppData, display: Option<&str>) -> Result<()> {
+    let name = state_name(display);
+    let shown: Shown = state::load(&name)?;
+    if restore(app_data, &shown)? {
+        return state::remove(app_data, &name);
+    }
+
+    let output = match display {
+        Some(display) => {
+            let Some((output, _)) = output::find(app_data, display) else {
+                bail!(Error::NotFound(format!("unknown display: {display}")));
+            };
+            Some(output)
+        }
+        None => None,
+    };
+
+    let active: Vec<_> = app_data
+        .workspace_state
+        .workspaces()
+        .filter(|w| w.state.contains(WorkspaceState::Active))
+        .filter(|w| {
+            output.as_ref().is_none_or(|output| {
+                workspace::get_groups_for_workspace(w, app_data)
+                    .any(|group| group.outputs.contains(output))
+            })
+        })
+        .map(|w| &w.handle)
+        .collect();
+    let toplevels: Vec<_> = app_data
+        .toplevel_info_state
+        .toplevels()
+        .filter(|t| !t.state.contains(&State::Minimized))
+        .filter(|t| t.workspace.iter().any(|w| active.contains(&w)))
+        .collect();
+    if toplevels.is_empty() {
+        bail!("no toplevel to minimize on the active workspaces");
+    }
+
+    let focused = toplevels
+        .iter()
+        .find(|t| t.state.contains(&State::Activated))
+        .map(|t| t.identifier.clone());
+    for toplevel in toplevels.iter() {
+        toplevel::minimize(app_data, toplevel, SetStateAction::Set)?;
+    }
+
+    state::save(
+        app_data,
+        &name,
+        &Shown {
+            minimized: toplevels.iter().map(|t| t.identifier.clone()).collect(),
+            focused,
+        },
+    )
+}
+
+/// Unminimize the toplevels in `shown` that still exist and focus the one
+/// that had focus before.
+///
+/// Returns false if none of them exist anymore.
+fn restore(app_data: &AppData, shown: &Shown) -> Result<bool> {
+    let toplevels: Vec<_> = app_data
+        .toplevel_info_state
+        .toplevels()
+        .filter(|t| shown.minimized.contains(&t.identifier))
+        .collect();
+    if toplevels.is_empty() {
+        return Ok(false);
+    }
+
+    for toplevel in toplevels.iter() {
+        if toplevel.state.contains(&State::Minimized) {
+            toplevel::minimize(app_data, toplevel, SetStateAction::Unset)?;
+        }
+    }
+
+    let focused = shown
+        .focused
+        .as_ref()
+        .and_then(|id| toplevels.iter().find(|t| &t.identifier == id));
+    match focused {
+        Some(toplevel) => toplevel::activate(app_data, toplevel)?,
+        None => debug!("no previously focused toplevel to activate"),
+    }
+    Ok(true)
+}
@@ -0,0 +1,231 @@
+use std::{io::Write, time::Duration};
+
+use anyhow::Result;
+use cosmic_protocols::{
+    toplevel_management::v1::client::zcosmic_toplevel_manager_v1::ZcosmicToplelevelManagementCapabilitiesV1 as ToplevelCapability,
+    workspace::v2::client::zcosmic_workspace_handle_v2::WorkspaceCapabilities,
+};
+use log::warn;
+use wayland_client::WEnum;
+use wayland_protocols::ext::workspace::v1::client::ext_workspace_handle_v1::WorkspaceCapabilities as ExtWorkspaceCapabilities;
+
+use crate::{
+    cosmic::{self, AppData, REQUIRED_GLOBALS, Session},
+    print::{Print, PrintList},
+};
+
+/// Globals conductor binds if they are available.
+const OPTIONAL_GLOBALS: &[&str] = &["wl_output", "wl_seat"];
+
+enum Status {
+    Available,
+    Degraded(String),
+    Unavailable(String),
+}
+
+pub fn report<W: Write>(printer: &mut impl Print<W>, timeout: Duration) -> Result<()> {
+    let (globals, event_queue) = cosmic::connect_globals()?;
+    let advertised = globals.contents().clone_list();
+    let missing = cosmic::missing_globals(&globals);
+
+    {
+        let mut printer = printer.sub_list("Globals")?;
+        for global in advertised.iter() {
+            let mut printer = printer.sub_struct()?;
+            printer.field("Interface", &global.interface)?;
+            printer.field("Version", global.version)?;
+            let used = REQUIRED_GLOBALS
+                .iter()
+                .chain(OPTIONAL_GLOBALS)
+                .any(|g| *g == global.interface);
+            printer.field("Used", used)?;
+        }
+    }
+    printer.inline_list("Missing Globals", missing.iter())?;
+
+    let session = if missing.is_empty() {
+        Session::from_globals(&globals, event_queue, timeout)
+            .inspect_err(|err| warn!("failed to receive compositor state: {err}"))
+            .ok()
+    } else {
+        None
+    };
+    let app_data = session.as_ref().map(|s| &s.app_data);
+
+    if let Some(app_data) = app_data {
+        printer.inline_list(
+            "Toplevel Capabilities",
+            app_data.toplevel_capabilities.iter().map(|cap| match cap {
+                WEnum::Value(cap) => format!("{cap:?}"),
+                WEnum::Unknown(value) => format!("unknown({value})"),
+            }),
+        )?;
+        let workspace_count = app_data.workspace_state.workspaces().count();
+        let mut printer = printer.sub_list("Workspace Capabilities")?;
+        for (name, count) in workspace_capability_counts(app_data) {
+            let mut printer = printer.sub_struct()?;
+            printer.field("Capability", name)?;
+            printer.field("Workspaces", format!("{count}/{workspace_count}"))?;
+        }
+    }
+
+    let mut printer = printer.sub_list("Commands")?;
+    for (command, status) in command_status(app_data, &missing) {
+        let mut printer = printer.sub_struct()?;
+        printer.field("Command", command)?;
+        match status {
+            Status::Available => printer.field("Status", "available")?,
+            Status::Degraded(reason) => {
+                printer.field("Status", "degraded")?;
+                printer.field("Reason", reason)?;
+            }
+            Status::Unavailable(reason) => {
+                printer.field("Status", "unavailable")?;
+                printer.field("Reason", reason)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn workspace_capability_counts(app_data: &AppData) -> Vec<(&'static str, usize)> {
+    let cosmic = |cap: WorkspaceCapabilities| {
+        app_data
+            .workspace_state
+            .workspaces()
+            .filter(|w| w.cosmic_capabilities.contains(cap))
+            .count()
+    };
+    let ext = |cap: ExtWorkspaceCapabilities| {
+        app_data
+            .workspace_state
+            .workspaces()
+            .filter(|w| w.capabilities.contains(cap))
+            .count()
+    };
+    vec![
+        ("move", cosmic(WorkspaceCapabilities::Move)),
+        ("pin", cosmic(WorkspaceCapabilities::Pin)),
+        ("rename", cosmic(WorkspaceCapabilities::Rename)),
+        ("set tiling", cosmic(WorkspaceCapabilities::SetTilingState)),
+        ("activate", ext(ExtWorkspaceCapabilities::Activate)),
+        ("deactivate", ext(ExtWorkspaceCapabilities::Deactivate)),
+        ("remove", ext(ExtWorkspaceCapabilities::Remove)),
+        ("assign", ext(ExtWorkspaceCapabilities::Assign)),
+    ]
+}
+
+fn command_status(
+    app_data: Option<&AppData>,
+    missing: &[&'static str],
+) -> Vec<(&'static str, Status)> {
+    let requires = |globals: &[&str]| -> Option<Status> {
+        let absent: Vec<&str> = globals
+            .iter()
+            .copied()
+            .filter(|g| missing.iter().any(|m| m == g))
+            .collect();
+        if absent.is_empty() {
+            None
+        } else {
+            Some(Status::Unavailable(format!(
+                "missing {}",
+                absent.join(", ")
+            )))
+        }
+    };
+
+    let toplevel_globals = &["ext_foreign_toplevel_list_v1", "zcosmic_toplevel_info_v1"][..];
+    let manager_globals = &[
+        "ext_foreign_toplevel_list_v1",
+        "zcosmic_toplevel_info_v1",
+        "zcosmic_toplevel_manager_v1",
+    ][..];
+    let workspace_globals = &["ext_workspace_manager_v1", "zcosmic_workspace_manager_v2"][..];
+
+    let toplevel_cap = |cap: ToplevelCapability| -> Status {
+        if let Some(status) = requires(manager_globals) {
+            return status;
+        }
+        let Some(app_data) = app_data else {
+            return Status::Degraded("compositor state not received".into());
+        };
+        if app_data.toplevel_capabilities.contains(&WEnum::Value(cap)) {
+            Status::Available
+        } else {
+            Status::Unavailable(format!("toplevel manager lacks capability {cap:?}"))
+        }
+    };
+
+    let workspace_cap = |name: &'static str| -> Status {
+        if let Some(status) = requires(workspace_globals) {
+            return status;
+        }
+        let Some(app_data) = app_data else {
+            return Status::Degraded("compositor state not received".into());
+        };
+        let total = app_data.workspace_state.workspaces().count();
+        let count = workspace_capability_counts(app_data)
+            .into_iter()
+            .find(|(cap, _)| *cap == name)
+            .map(|(_, count)| count)
+            .unwrap_or(0);
+        if count == 0 {
+            Status::Unavailable(format!("no workspace supports {name}"))
+        } else if count < total {
+            Status::Degraded(format!("only {count} of {total} workspaces support {name}"))
+        } else {
+            Status::Available
+        }
+    };
+
+    let globals_only = |globals: &[&str]| requires(globals).unwrap_or(Status::Available);
+
+    vec![
+        ("outputs", globals_only(&[])),
+        ("workspace-groups", globals_only(workspace_globals)),
+        ("workspaces list", globals_only(workspace_globals)),
+        ("workspaces move-to-pos", workspace_cap("move")),
+        ("workspaces move-to-display", workspace_cap("move")),
+        ("workspaces pin", workspace_cap("pin")),
+        ("workspaces unpin", workspace_cap("pin")),
+        ("workspaces activate", workspace_cap("activate")),
+        ("workspaces swap", workspace_cap("move")),
+        ("workspaces reorder", workspace_cap("move")),
+        ("workspaces sort", workspace_cap("move")),
+        ("toplevels list", globals_only(toplevel_globals)),
+        ("toplevels max", toplevel_cap(ToplevelCapability::Maximize)),
+        ("toplevels min", toplevel_cap(ToplevelCapability::Minimize)),
+        (
+            "toplevels fullscreen",
+            toplevel_cap(ToplevelCapability::Fullscreen),
+        ),
+        ("toplevels sticky", toplevel_cap(ToplevelCapability::Sticky)),
+        (
+            "toplevels activate",
+            toplevel_cap(ToplevelCapability::Activate),
+        ),
+        ("toplevels close", toplevel_cap(ToplevelCapability::Close)),
+        (
+            "toplevels move",
+            toplevel_cap(ToplevelCapability::MoveToWorkspace),
+        ),
+        (
+            "toplevels summon",
+            toplevel_cap(ToplevelCapability::MoveToWorkspace),
+        ),
+        (
+            "toplevels swap",
+            toplevel_cap(ToplevelCapability::MoveToWorkspace),
+        ),
+        ("mark", globals_only(toplevel_globals)),
+        ("show-desktop", toplevel_cap(ToplevelCapability::Minimize)),
+        ("scratchpad send", toplevel_cap(ToplevelCapability::Sticky)),
+        (
+            "scratchpad show",
+            toplevel_cap(ToplevelCapability::MoveToWorkspace),
+        ),
+        ("snapshot", globals_only(&[])),
+    ]
+}
@@ -0,0 +1,131 @@
+use std::fmt::Display;
+
+use serde_json::json;
+
+/// Errors a calling script can tell apart by the exit code.
+///
+/// They are passed around as [anyhow::Error] like every other error, so
+/// context can be added without losing the exit code.
+///
+/// | exit code | error                          |
+/// |-----------|--------------------------------|
+/// | 1         | any other failure              |
+/// | 2         | invalid arguments              |
+/// | 3         | [Error::NoCompositor]          |
+/// | 4         | [Error::ProtocolUnavailable]   |
+/// | 5         | [Error::Timeout]               |
+/// | 6         | [Error::NotFound]              |
+/// | 7         | [Error::Ambiguous]             |
+/// | 8         | [Error::CapabilityMissing]     |
+/// | 9         | [Error::Internal]              |
+///
+/// 3 to 5 are the codes conductor always used for connection problems and
+/// must not change.
+#[derive(Debug)]
+pub enum Error {
+    /// no toplevel, workspace, display or mark matches
+    NotFound(String),
+    /// a name or id matches more than one toplevel or workspace
+    Ambiguous(String),
+    /// the compositor does not allow the action on the toplevel or workspace
+    CapabilityMissing(String),
+    /// there is no compositor to connect to
+    NoCompositor(String),
+    /// the compositor lacks required protocols
+    ProtocolUnavailable(String),
+    /// the compositor did not send its state or apply a change in time
+    Timeout(String),
+    /// the state received from the compositor is inconsistent
+    Internal(String),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::NoCompositor(_) => 3,
+            Error::ProtocolUnavailable(_) => 4,
+            Error::Timeout(_) => 5,
+            Error::NotFound(_) => 6,
+            Error::Ambiguous(_) => 7,
+            Error::CapabilityMissing(_) => 8,
+            Error::Internal(_) => 9,
+        }
+    }
+
+    /// The name of the error in json output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::NotFound(_) => "not_found",
+            Error::Ambiguous(_) => "ambiguous",
+            Error::CapabilityMissing(_) => "capability_missing",
+            Error::NoCompositor(_) => "no_compositor",
+            Error::ProtocolUnavailable(_) => "protocol_unavailable",
+            Error::Timeout(_) => "timeout",
+            Error::Internal(_) => "internal",
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotFound(message)
+            | Error::Ambiguous(message)
+            | Error::CapabilityMissing(message)
+            | Error::NoCompositor(message)
+            | Error::ProtocolUnavailable(message)
+            | Error::Timeout(message)
+            | Error::Internal(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The [Error] anywhere in the chain of `err`.
+fn find(err: &anyhow::Error) -> Option<&Error> {
+    err.chain().find_map(|err| err.downcast_ref::<Error>())
+}
+
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    find(err).map_or(1, Error::exit_code)
+}
+
+/// `err` as a single json object, printed instead of the message with `--format json`.
+pub fn to_json(err: &anyhow::Error) -> String {
+    json!({
+        "error": {
+            "kind": find(err).map_or("other", Error::kind),
+            "message": format!("{err:#}"),
+            "exit_code": exit_code(err),
+        }
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{Context, anyhow};
+
+    use super::*;
+
+    #[test]
+    fn exit_code_of_the_chain() {
+        assert_eq!(exit_code(&anyhow!("something else")), 1);
+        assert_eq!(exit_code(&Error::Timeout(String::new()).into()), 5);
+        let err = Err::<(), _>(Error::Ambiguous("two toplevels".to_string()))
+            .context("failed to activate")
+            .unwrap_err();
+        assert_eq!(exit_code(&err), 7);
+        assert_eq!(format!("{err:#}"), "failed to activate: two toplevels");
+    }
+
+    #[test]
+    fn json_includes_kind_and_exit_code() {
+        let err = anyhow::Error::from(Error::NoCompositor("no socket".to_string()));
+        let json: serde_json::Value = serde_json::from_str(&to_json(&err)).unwrap();
+        assert_eq!(json["error"]["kind"], "no_compositor");
+        assert_eq!(json["error"]["exit_code"], 3);
+        assert_eq!(json["error"]["message"], "no socket");
+    }
+}
@@ -0,0 +1,422 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use cosmic_client_toolkit::{
+    sctk::output::OutputInfo, toplevel_info::ToplevelInfo, workspace::Workspace,
+};
+use cosmic_protocols::toplevel_info::v1::client::zcosmic_toplevel_handle_v1::State as ToplevelState;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use wayland_client::{Proxy, protocol::wl_output::WlOutput};
+use wayland_protocols::ext::{
+    foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+    workspace::v1::client::ext_workspace_handle_v1::State as WorkspaceState,
+};
+
+use crate::{cosmic::AppData, output, workspace};
+
+/// A raw notification from one of the wayland handlers in `cosmic.rs`.
+///
+/// Changes are only recorded once [AppData::changes] is set and are turned
+/// into [Event]s by a [Tracker].
+#[derive(Debug, Clone)]
+pub enum Change {
+    ToplevelOpened(ExtForeignToplevelHandleV1),
+    ToplevelUpdated(ExtForeignToplevelHandleV1),
+    ToplevelClosed(ExtForeignToplevelHandleV1),
+    WorkspacesDone,
+    OutputAdded(WlOutput),
+    OutputUpdated(WlOutput),
+    OutputRemoved(WlOutput),
+}
+
+/// A [Change] together with the state it refers to, as `conductor record` writes it.
+///
+/// Objects are identified by their wayland object id. The state is taken when
+/// the change is processed, so a recording can be fed to a [Tracker] again
+/// without a compositor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum ObservedChange {
+    ToplevelOpened {
+        object: String,
+        toplevel: Option<ToplevelSnapshot>,
+    },
+    ToplevelUpdated {
+        object: String,
+        toplevel: Option<ToplevelSnapshot>,
+    },
+    ToplevelClosed {
+        object: String,
+        toplevel: Option<ToplevelSnapshot>,
+    },
+    WorkspacesDone {
+        workspaces: BTreeMap<String, WorkspaceSnapshot>,
+    },
+    OutputAdded {
+        object: String,
+        output: Option<OutputSnapshot>,
+    },
+    OutputUpdated {
+        object: String,
+        output: Option<OutputSnapshot>,
+    },
+    OutputRemoved {
+        object: String,
+        output: Option<OutputSnapshot>,
+    },
+}
+
+/// Something that happened in the compositor, e.g. a window was opened.
+///
+/// Serialized with the event name as "event", like `conductor record` writes it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    ToplevelOpened(ToplevelSnapshot),
+    ToplevelChanged(ToplevelSnapshot),
+    ToplevelClosed(ToplevelSnapshot),
+    ToplevelFocused(ToplevelSnapshot),
+    WorkspaceActivated(WorkspaceSnapshot),
+    WorkspaceUrgent(WorkspaceSnapshot),
+    OutputAdded(OutputSnapshot),
+    OutputChanged(OutputSnapshot),
+    OutputRemoved(OutputSnapshot),
+}
+
+/// The state of a toplevel at the time of an event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToplevelSnapshot {
+    pub identifier: String,
+    pub title: String,
+    pub app_id: String,
+    pub workspaces: Vec<String>,
+    pub outputs: Vec<String>,
+    pub activated: bool,
+}
+
+/// The state of a workspace at the time of an event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkspaceSnapshot {
+    pub name: String,
+    pub outputs: Vec<String>,
+    pub active: bool,
+    pub urgent: bool,
+}
+
+/// The state of an output at the time of an event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputSnapshot {
+    pub display: String,
+    pub description: Option<String>,
+    pub x: i32,
+    pub y: i32,
+    pub logical_size: Option<(i32, i32)>,
+}
+
+impl Event {
+    /// All event names, as used in the hook config.
+    pub const NAMES: &[&str] = &[
+        "toplevel_opened",
+        "toplevel_changed",
+        "toplevel_closed",
+        "toplevel_focused",
+        "workspace_activated",
+        "workspace_urgent",
+        "output_added",
+        "output_changed",
+        "output_removed",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::ToplevelOpened(_) => "toplevel_opened",
+            Event::ToplevelChanged(_) => "toplevel_changed",
+            Event::ToplevelClosed(_) => "toplevel_closed",
+            Event::ToplevelFocused(_) => "toplevel_focused",
+            Event::WorkspaceActivated(_) => "workspace_activated",
+            Event::WorkspaceUrgent(_) => "workspace_urgent",
+            Event::OutputAdded(_) => "output_added",
+            Event::OutputChanged(_) => "output_changed",
+            Event::OutputRemoved(_) => "output_removed",
+        }
+    }
+
+    /// The properties of the event by name.
+    ///
+    /// Lists like the workspaces of a toplevel are joined with ",".
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Event::ToplevelOpened(toplevel)
+            | Event::ToplevelChanged(toplevel)
+            | Event::ToplevelClosed(toplevel)
+            | Event::ToplevelFocused(toplevel) => vec![
+                ("id", toplevel.identifier.clone()),
+                ("title", toplevel.title.clone()),
+                ("app_id", toplevel.app_id.clone()),
+                ("workspace", toplevel.workspaces.join(",")),
+                ("output", toplevel.outputs.join(",")),
+            ],
+            Event::WorkspaceActivated(workspace) | Event::WorkspaceUrgent(workspace) => vec![
+                ("workspace", workspace.name.clone()),
+                ("output", workspace.outputs.join(",")),
+            ],
+            Event::OutputAdded(output)
+            | Event::OutputChanged(output)
+            | Event::OutputRemoved(output) => vec![("output", output.display.clone())],
+        }
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            self.name(),
+            self.fields()
+                .iter()
+                .map(|(name, value)| format!("{name}={value:?}"))
+                .join(" ")
+        )
+    }
+}
+
+/// Turns [Change]s into [Event]s by comparing against the last known state.
+///
+/// Serialized as the start of a recording, so replaying it produces the same events.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Tracker {
+    toplevels: BTreeMap<String, ToplevelSnapshot>,
+    workspaces: BTreeMap<String, WorkspaceSnapshot>,
+    outputs: BTreeMap<String, OutputSnapshot>,
+}
+
+impl Tracker {
+    /// Start tracking from the current state and record changes from now on.
+    ///
+    /// Toplevels, workspaces and outputs that already exist don't produce events.
+    pub fn new(app_data: &mut AppData) -> Self {
+        let toplevels = app_data
+            .toplevel_info_state
+            .toplevels()
+            .map(|t| {
+                (
+                    t.foreign_toplevel.id().to_string(),
+                    ToplevelSnapshot::new(app_data, t),
+                )
+            })
+            .collect();
+        let outputs = app_data
+            .output_state
+            .outputs()
+            .filter_map(|o| Some((o.id().to_string(), snapshot_output(app_data, &o)?)))
+            .collect();
+        app_data.changes = Some(Vec::new());
+        Self {
+            toplevels,
+            workspaces: snapshot_workspaces(app_data),
+            outputs,
+        }
+    }
+
+    /// Take all changes recorded since the last call and return the resulting events.
+    pub fn process(&mut self, app_data: &mut AppData) -> Vec<Event> {
+        self.apply(observe(app_data))
+    }
+
+    /// The events that result from `changes`, like [Tracker::process] but
+    /// without a compositor.
+    pub fn apply(&mut self, changes: Vec<ObservedChange>) -> Vec<Event> {
+        let mut events = Vec::new();
+        for change in changes {
+            match change {
+                ObservedChange::ToplevelOpened { object, toplevel } => {
+                    let Some(toplevel) = toplevel else {
+                        continue;
+                    };
+                    self.toplevels.insert(object, toplevel.clone());
+                    let focused = toplevel.activated;
+                    events.push(Event::ToplevelOpened(toplevel.clone()));
+                    if focused {
+                        events.push(Event::ToplevelFocused(toplevel));
+                    }
+                }
+                ObservedChange::ToplevelUpdated { object, toplevel } => {
+                    let Some(toplevel) = toplevel else {
+                        continue;
+                    };
+                    let previous = self.toplevels.insert(object, toplevel.clone());
+                    if previous.as_ref() == Some(&toplevel) {
+                        continue;
+                    }
+                    let focused = toplevel.activated && !previous.is_some_and(|p| p.activated);
+                    events.push(Event::ToplevelChanged(toplevel.clone()));
+                    if focused {
+                        events.push(Event::ToplevelFocused(toplevel));
+                    }
+                }
+                ObservedChange::ToplevelClosed { object, toplevel } => {
+                    if let Some(toplevel) = self.toplevels.remove(&object).or(toplevel) {
+                        events.push(Event::ToplevelClosed(toplevel));
+                    }
+                }
+                ObservedChange::WorkspacesDone { workspaces } => {
+                    for (object, workspace) in workspaces.iter() {
+                        let previous = self.workspaces.get(object);
+                        if workspace.active && !previous.is_some_and(|p| p.active) {
+                            events.push(Event::WorkspaceActivated(workspace.clone()));
+                        }
+                        if workspace.urgent && !previous.is_some_and(|p| p.urgent) {
+                            events.push(Event::WorkspaceUrgent(workspace.clone()));
+                        }
+                    }
+                    self.workspaces = workspaces;
+                }
+                ObservedChange::OutputAdded { object, output } => {
+                    let Some(output) = output else {
+                        continue;
+                    };
+                    self.outputs.insert(object, output.clone());
+                    events.push(Event::OutputAdded(output));
+                }
+                ObservedChange::OutputUpdated { object, output } => {
+                    let Some(output) = output else {
+                        continue;
+                    };
+                    let previous = self.outputs.insert(object, output.clone());
+                    if previous.as_ref() != Some(&output) {
+                        events.push(Event::OutputChanged(output));
+                    }
+                }
+                ObservedChange::OutputRemoved { object, output } => {
+                    if let Some(output) = self.outputs.remove(&object).or(output) {
+                        events.push(Event::OutputRemoved(output));
+                    }
+                }
+            }
+        }
+        events
+    }
+}
+
+/// Take all changes recorded since the last call together with the current state.
+pub fn observe(app_data: &mut AppData) -> Vec<ObservedChange> {
+    let changes = app_data
+        .changes
+        .as_mut()
+        .map(std::mem::take)
+        .unwrap_or_default();
+
+    changes
+        .into_iter()
+        .map(|change| match change {
+            Change::ToplevelOpened(handle) => ObservedChange::ToplevelOpened {
+                object: handle.id().to_string(),
+                toplevel: snapshot_toplevel(app_data, &handle),
+            },
+            Change::ToplevelUpdated(handle) => ObservedChange::ToplevelUpdated {
+                object: handle.id().to_string(),
+                toplevel: snapshot_toplevel(app_data, &handle),
+            },
+            Change::ToplevelClosed(handle) => ObservedChange::ToplevelClosed {
+                object: handle.id().to_string(),
+                toplevel: snapshot_toplevel(app_data, &handle),
+            },
+            Change::WorkspacesDone => ObservedChange::WorkspacesDone {
+                workspaces: snapshot_workspaces(app_data),
+            },
+            Change::OutputAdded(output) => ObservedChange::OutputAdded {
+                object: output.id().to_string(),
+                output: snapshot_output(app_data, &output),
+            },
+            Change::OutputUpdated(output) => ObservedChange::OutputUpdated {
+                object: output.id().to_string(),
+                output: snapshot_output(app_data, &output),
+            },
+            Change::OutputRemoved(output) => ObservedChange::OutputRemoved {
+                object: output.id().to_string(),
+                output: snapshot_output(app_data, &output),
+            },
+        })
+        .collect()
+}
+
+fn snapshot_toplevel(
+    app_data: &AppData,
+    handle: &ExtForeignToplevelHandleV1,
+) -> Option<ToplevelSnapshot> {
+    app_data
+        .toplevel_info_state
+        .info(handle)
+        .map(|info| ToplevelSnapshot::new(app_data, info))
+}
+
+fn snapshot_workspaces(app_data: &AppData) -> BTreeMap<String, WorkspaceSnapshot> {
+    app_data
+        .workspace_state
+        .workspaces()
+        .map(|w| {
+            (
+                w.handle.id().to_string(),
+                WorkspaceSnapshot::new(app_data, w),
+            )
+        })
+        .collect()
+}
+
+fn snapshot_output(app_data: &AppData, output: &WlOutput) -> Option<OutputSnapshot> {
+    app_data
+        .output_state
+        .info(output)
+        .map(|info| OutputSnapshot::new(&info))
+}
+
+impl ToplevelSnapshot {
+    pub fn new(app_data: &AppData, toplevel: &ToplevelInfo) -> Self {
+        Self {
+            identifier: toplevel.identifier.clone(),
+            title: toplevel.title.clone(),
+            app_id: toplevel.app_id.clone(),
+            workspaces: toplevel
+                .workspace
+                .iter()
+                .filter_map(|w| app_data.workspace_state.workspace_info(w))
+                .map(|w| w.name.clone())
+                .collect(),
+            outputs: toplevel
+                .output
+                .iter()
+                .filter_map(|o| app_data.output_state.info(o))
+                .map(|o| output::display_name(&o))
+                .collect(),
+            activated: toplevel.state.contains(&ToplevelState::Activated),
+        }
+    }
+}
+
+impl WorkspaceSnapshot {
+    pub fn new(app_data: &AppData, workspace: &Workspace) -> Self {
+        Self {
+            name: workspace.name.clone(),
+            outputs: workspace::get_groups_for_workspace(workspace, app_data)
+                .flat_map(|group| group.outputs.iter())
+                .filter_map(|o| app_data.output_state.info(o))
+                .map(|o| output::display_name(&o))
+                .collect(),
+            active: workspace.state.contains(WorkspaceState::Active),
+            urgent: workspace.state.contains(WorkspaceState::Urgent),
+        }
+    }
+}
+
+impl OutputSnapshot {
+    pub fn new(output: &OutputInfo) -> Self {
+        Self {
+            display: output::display_name(output),
+            description: output.description.clone(),
+            x: output.location.0,
+            y: output.location.1,
+            logical_size: output.logical_size,
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use std::io::Write;
+
+use anyhow::{Context, Result, bail};
+use cosmic_client_toolkit::toplevel_info::ToplevelInfo;
+use cosmic_protocols::toplevel_info::v1::client::zcosmic_toplevel_handle_v1::State;
+use itertools::Itertools;
+use log::debug;
+use wayland_client::protocol::wl_output::WlOutput;
+use wayland_protocols::ext::workspace::v1::client::ext_workspace_handle_v1::State as WorkspaceState;
+
+use crate::{
+    args::Direction,
+    cosmic::AppData,
+    error::Error,
+    event::Event,
+    ipc, output,
+    print::{Print, PrintList},
+    snapshot::Snapshot,
+    toplevel,
+};
+
+/// The request the daemon answers with the focus history.
+pub const HISTORY_REQUEST: &str = "focus-history";
+
+/// Toplevel identifiers from the most to the least recently focused, kept by the daemon.
+#[derive(Debug, Default)]
+pub struct History {
+    identifiers: Vec<String>,
+}
+
+impl History {
+    /// Start the history with the toplevel that currently has focus.
+    pub fn new(app_data: &AppData) -> Self {
+        Self::from_snapshot(&Snapshot::new(app_data))
+    }
+
+    /// Start the history with the toplevel that had focus in `snapshot`.
+    pub fn from_snapshot(snapshot: &Snapshot) -> Self {
+        Self {
+            identifiers: snapshot
+                .toplevels
+                .iter()
+                .filter(|t| t.has_state(State::Activated))
+                .map(|t| t.identifier.clone())
+                .collect(),
+        }
+    }
+
+    pub fn record(&mut self, event: &Event) {
+        match event {
+            Event::ToplevelFocused(toplevel) => {
+                self.identifiers.retain(|id| *id != toplevel.identifier);
+                self.identifiers.insert(0, toplevel.identifier.clone());
+            }
+            Event::ToplevelClosed(toplevel) => {
+                self.identifiers.retain(|id| *id != toplevel.identifier);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn identifiers(&self) -> &[String] {
+        &self.identifiers
+    }
+}
+
+/// The toplevels in the daemons focus history that still exist, most recent first.
+fn history(app_data: &AppData) -> Result<Vec<&ToplevelInfo>> {
+    let identifiers = ipc::request(HISTORY_REQUEST)?;
+    Ok(identifiers
+        .iter()
+        .filter_map(|id| {
+            app_data
+                .toplevel_info_state
+                .toplevels()
+                .find(|t| &t.identifier == id)
+        })
+        .collect())
+}
+
+fn is_focused(toplevel: &ToplevelInfo) -> bool {
+    toplevel.state.contains(&State::Activated)
+}
+
+pub fn print_history<W: Write>(app_data: &AppData, printer: &mut impl Print<W>) -> Result<()> {
+    let snapshot = Snapshot::new(app_data);
+    let mut printer = printer.sub_list("Focus History")?;
+    for toplevel in history(app_data)? {
+        let Some(toplevel) = snapshot
+            .toplevels
+            .iter()
+            .find(|t| t.identifier == toplevel.identifier)
+        else {
+            continue;
+        };
+        toplevel::print_toplevel(&snapshot, &mut printer.sub_struct()?, toplevel, false)?;
+    }
+    Ok(())
+}
+
+/// Activate the toplevel that had focus before the current one.
+pub fn last(app_data: &AppData) -> Result<()> {
+    let Some(toplevel) = history(app_data)?.into_iter().find(|t| !is_focused(t)) else {
+        bail!(Error::NotFound(
+            "no previously focused toplevel".to_string()
+        ));
+    };
+    toplevel::activate(app_data, toplevel)
+}
+
+/// Activate the toplevel that was focused the longest time ago, optionally
+/// limited to toplevels with `app_id`.
+///
+/// Toplevels that were never focused while the daemon was running come first,
+/// so calling this repeatedly cycles through all of them.
+pub fn cycle(app_data: &AppData, app_id: Option<&str>) -> Result<()> {
+    let history = history(app_data)?;
+    let candidates: Vec<_> = app_data
+        .toplevel_info_state
+        .toplevels()
+        .filter(|t| app_id.is_none_or(|app_id| t.app_id == app_id))
+        .collect();
+
+    let never_focused = candidates
+        .iter()
+        .filter(|t| !history.iter().any(|h| h.identifier == t.identifier));
+    let least_recent = history
+        .iter()
+        .rev()
+        .filter(|h| candidates.iter().any(|t| t.identifier == h.identifier));
+    let next = never_focused
+        .chain(least_recent)
+        .find(|t| !is_focused(t))
+        .or(candidates.first());
+
+    let Some(toplevel) = next else {
+        match app_id {
+            Some(app_id) => bail!(Error::NotFound(format!("no toplevel with app id {app_id}"))),
+            None => bail!(Error::NotFound("no toplevels".to_string())),
+        }
+    };
+    toplevel::activate(app_data, toplevel)
+}
+
+fn focused(app_data: &AppData) -> Result<&ToplevelInfo> {
+    app_data
+        .toplevel_info_state
+        .toplevels()
+        .find(|t| is_focused(t))
+        .context("no toplevel has focus")
+}
+
+/// Activate the nearest visible toplevel in `direction` on the workspace of the focused toplevel.
+///
+/// Distances are measured between the centers of the toplevels, with the
+/// offset across the direction counting double, so toplevels that are
+/// roughly in line are preferred.
+pub fn direction(app_data: &AppData, direction: Direction) -> Result<()> {
+    let current = focused(app_data)?;
+    let output = current
+        .output
+        .iter()
+        .exactly_one()
+        .ok()
+        .context("the focused toplevel is not on exactly one display")?;
+    let Some(from) = center(current, output) else {
+        bail!(Error::Internal(
+            "INTERNAL: no geometry for the focused toplevel".to_string()
+        ));
+    };
+
+    let next = app_data
+        .toplevel_info_state
+        .toplevels()
+        .filter(|t| t.identifier != current.identifier)
+        .filter(|t| !t.state.contains(&State::Minimized))
+        .filter(|t| t.workspace.iter().any(|w| current.workspace.contains(w)))
+        .filter_map(|t| {
+            let to = center(t, output)?;
+            score(direction, from, to).map(|score| (score, t))
+        })
+        .min_by_key(|(score, _)| *score)
+        .map(|(_, t)| t);
+
+    let Some(next) = next else {
+        bail!(Error::NotFound(format!(
+            "no toplevel {direction:?} of the focused toplevel"
+        )));
+    };
+    toplevel::activate(app_data, next)
+}
+
+/// Activate a toplevel on the nearest display in `direction`, based on the display locations.
+///
+/// Prefers the most recently focused toplevel if the daemon is running.
+pub fn output(app_data: &AppData, direction: Direction) -> Result<()> {
+    let current = focused(app_data)?;
+    let current_output = current
+        .output
+        .iter()
+        .exactly_one()
+        .ok()
+        .context("the focused toplevel is not on exactly one display")?;
+    let Some(from) = output_center(app_data, current_output) else {
+        bail!(Error::Internal(
+            "INTERNAL: no information for the display of the focused toplevel".to_string()
+        ));
+    };
+
+    let target = app_data
+        .output_state
+        .outputs()
+        .filter(|o| o != current_output)
+        .filter_map(|o| {
+            let to = output_center(app_data, &o)?;
+            score(direction, from, to).map(|score| (score, o))
+        })
+        .min_by_key(|(score, _)| *score)
+        .map(|(_, o)| o);
+    let Some(target) = target else {
+        bail!(Error::NotFound(format!(
+            "no display {direction:?} of the current one"
+        )));
+    };
+
+    let candidates: Vec<_> = app_data
+        .toplevel_info_state
+        .toplevels()
+        .filter(|t| t.output.contains(&target))
+        .filter(|t| !t.state.contains(&State::Minimized))
+        .filter(|t| {
+            t.workspace
+                .iter()
+                .filter_map(|w| app_data.workspace_state.workspace_info(w))
+                .any(|w| w.state.contains(WorkspaceState::Active))
+        })
+        .collect();
+
+    let history = ipc::request(HISTORY_REQUEST)
+        .inspect_err(|err| debug!("no focus history: {err:#}"))
+        .unwrap_or_default();
+    let next = history
+        .iter()
+        .find_map(|id| candidates.iter().find(|t| &t.identifier == id))
+        .or(candidates.first());
+
+    let Some(next) = next else {
+        let name = app_data
+            .output_state
+            .info(&target)
+            .map(|o| output::display_name(&o))
+            .unwrap_or_default();
+        bail!(Error::NotFound(format!(
+            "no visible toplevel on display {name}"
+        )));
+    };
+    toplevel::activate(app_data, next)
+}
+
+fn center(toplevel: &ToplevelInfo, output: &WlOutput) -> Option<(i32, i32)> {
+    let geometry = toplevel.geometry.get(output)?;
+    Some((
+        geometry.x + geometry.width / 2,
+        geometry.y + geometry.height / 2,
+    ))
+}
+
+fn output_center(app_data: &AppData, output: &WlOutput) -> Option<(i32, i32)> {
+    let info = app_data.output_state.info(output)?;
+    let (width, height) = info.logical_size.unwrap_or_default();
+    Some((info.location.0 + width / 2, info.location.1 + height / 2))
+}
+
+/// How far `to` is from `from` in `direction` or `None` if it is not in that direction.
+fn score(direction: Direction, from: (i32, i32), to: (i32, i32)) -> Option<i64> {
+    let dx = i64::from(to.0) - i64::from(from.0);
+    let dy = i64::from(to.1) - i64::from(from.1);
+    let (along, across) = match direction {
+        Direction::Left => (-dx, dy),
+        Direction::Right => (dx, dy),
+        Direction::Up => (-dy, dx),
+        Direction::Down => (dy, dx),
+    };
+    (along > 0).then_some(along + 2 * across.abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_ignores_other_directions() {
+        let from = (100, 100);
+        assert!(score(Direction::Left, from, (50, 100)).is_some());
+        assert!(score(Direction::Left, from, (150, 100)).is_none());
+        assert!(score(Direction::Right, from, (100, 100)).is_none());
+        assert!(score(Direction::Up, from, (100, 50)).is_some());
+        assert!(score(Direction::Down, from, (100, 50)).is_none());
+    }
+
+    #[test]
+    fn score_prefers_toplevels_in_line() {
+        let from = (0, 0);
+        assert_eq!(score(Direction::Right, from, (100, 0)), Some(100));
+        assert_eq!(score(Direction::Right, from, (100, -30)), Some(160));
+        assert_eq!(score(Direction::Down, from, (20, 100)), Some(140));
+        // farther away but in line beats closer but offset
+        assert!(score(Direction::Right, from, (150, 0)) < score(Direction::Right, from, (60, 50)));
+    }
+}
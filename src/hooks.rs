@@ -0,0 +1,182 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+};
+
+use anyhow::{Context, Result, bail};
+use itertools::Itertools;
+use log::warn;
+
+use crate::event::Event;
+
+/// Keys that can be used to filter events, see [Event::fields].
+///
+/// "name" is an alias for "workspace".
+const FILTER_KEYS: &[&str] = &["id", "title", "app_id", "workspace", "name", "output"];
+
+/// A shell command that runs whenever a matching event happens.
+///
+/// Hooks are written as `on <event> [key=value ...] run <command>`.
+#[derive(Debug, Clone)]
+pub struct Hook {
+    pub event: String,
+    pub filters: Vec<(String, String)>,
+    pub command: String,
+}
+
+/// The hook file in the users config directory.
+pub fn default_path() -> Option<PathBuf> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join("cosmic-conductor").join("hooks"))
+}
+
+/// Read all hooks from `path`. Empty lines and lines starting with '#' are ignored.
+pub fn load(path: &Path) -> Result<Vec<Hook>> {
+    let config = fs::read_to_string(path)
+        .with_context(|| format!("failed to read hooks from {}", path.display()))?;
+    config
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_nr, line)| {
+            parse(line).with_context(|| format!("{}:{line_nr}: invalid hook", path.display()))
+        })
+        .collect()
+}
+
+/// Read the hooks from `path`, or from [default_path] if no path is given.
+///
+/// Only a missing default file means there are no hooks, a given path has to exist.
+pub fn load_or_default(path: Option<PathBuf>) -> Result<Vec<Hook>> {
+    if let Some(path) = path {
+        return load(&path);
+    }
+    match default_path() {
+        Some(path) if path.exists() => load(&path),
+        Some(path) => {
+            warn!("no hooks configured, {} does not exist", path.display());
+            Ok(Vec::new())
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+fn parse(line: &str) -> Result<Hook> {
+    let Some(rest) = line.strip_prefix("on ") else {
+        bail!("expected \"on <event> [key=value ...] run <command>\"");
+    };
+    // the first "run" that is not inside quotes, the command after it is kept as written
+    let Some((words, command)) = rest
+        .match_indices(" run ")
+        .find_map(|(i, run)| Some((shlex::split(&rest[..i])?, &rest[i + run.len()..])))
+    else {
+        shlex::split(rest).context("invalid quoting")?;
+        bail!("missing \"run <command>\"");
+    };
+    let Some((event, filters)) = words.split_first() else {
+        bail!("missing event");
+    };
+    if !Event::NAMES.contains(&event.as_str()) {
+        bail!(
+            "unknown event \"{event}\", expected one of {}",
+            Event::NAMES.join(", ")
+        );
+    }
+
+    let filters = filters
+        .iter()
+        .map(|filter| {
+            let Some((key, value)) = filter.split_once('=') else {
+                bail!("expected key=value but got \"{filter}\"");
+            };
+            if !FILTER_KEYS.contains(&key) {
+                bail!(
+                    "unknown filter \"{key}\", expected one of {}",
+                    FILTER_KEYS.join(", ")
+                );
+            }
+            Ok((key.to_string(), value.to_string()))
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(Hook {
+        event: event.clone(),
+        filters,
+        command: command.trim().to_string(),
+    })
+}
+
+impl Hook {
+    pub fn matches(&self, event: &Event) -> bool {
+        if self.event != event.name() {
+            return false;
+        }
+        let fields = event.fields();
+        self.filters.iter().all(|(key, expected)| {
+            let key = if key == "name" { "workspace" } else { key };
+            fields
+                .iter()
+                .filter(|(name, _)| *name == key)
+                .any(|(_, value)| match key {
+                    // like on the command line the start of the id is enough
+                    "id" => value.starts_with(expected.as_str()),
+                    "workspace" | "output" => value.split(',').contains(&expected.as_str()),
+                    _ => value == expected,
+                })
+        })
+    }
+
+    /// Start the command with `sh -c`.
+    ///
+    /// The event is passed in the environment as `CONDUCTOR_EVENT` and one
+    /// `CONDUCTOR_<FIELD>` variable per field, e.g. `CONDUCTOR_APP_ID`.
+    pub fn spawn(&self, event: &Event) -> Result<Child> {
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .env("CONDUCTOR_EVENT", event.name())
+            .envs(
+                event
+                    .fields()
+                    .into_iter()
+                    .map(|(name, value)| (format!("CONDUCTOR_{}", name.to_uppercase()), value)),
+            )
+            .stdin(Stdio::null())
+            .spawn()?;
+        Ok(child)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_inside_quotes_is_part_of_a_filter() {
+        let hook =
+            parse("on toplevel_opened title=\"how to run it\" run notify-send \"run\"").unwrap();
+        assert_eq!(hook.event, "toplevel_opened");
+        assert_eq!(
+            hook.filters,
+            [("title".to_string(), "how to run it".to_string())]
+        );
+        assert_eq!(hook.command, "notify-send \"run\"");
+    }
+
+    #[test]
+    fn invalid_hooks() {
+        assert!(parse("on toplevel_opened title=\"unclosed run echo").is_err());
+        assert!(parse("on toplevel_opened echo").is_err());
+        assert!(parse("on no_such_event run echo").is_err());
+    }
+
+    #[test]
+    fn a_given_path_has_to_exist() {
+        let path = env::temp_dir().join("conductor-no-such-hooks");
+        assert!(load_or_default(Some(path)).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    os::{
+        fd::{AsFd, BorrowedFd},
+        unix::net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use log::{debug, warn};
+
+/// How long the daemon waits for a client to send its request.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Lines starting with this prefix report an error instead of a result.
+const ERROR_PREFIX: &str = "error: ";
+
+/// The directory for sockets and other files that only live as long as the session.
+pub fn runtime_dir() -> Result<PathBuf> {
+    let Some(dir) = env::var_os("XDG_RUNTIME_DIR") else {
+        bail!("XDG_RUNTIME_DIR is not set");
+    };
+    Ok(PathBuf::from(dir))
+}
+
+pub fn socket_path() -> Result<PathBuf> {
+    Ok(runtime_dir()?.join("cosmic-conductor.sock"))
+}
+
+/// The daemon side of the socket.
+///
+/// Clients send a single line and receive the response lines until the
+/// daemon closes the connection.
+pub struct Server {
+    listener: UnixListener,
+}
+
+impl Server {
+    /// Listen on [socket_path], replacing the socket of a daemon that is no longer running.
+    pub fn bind() -> Result<Self> {
+        let path = socket_path()?;
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                bail!("another daemon is already listening on {}", path.display());
+            }
+            debug!("removing stale socket {}", path.display());
+            fs::remove_file(&path)?;
+        }
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("failed to listen on {}", path.display()))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener })
+    }
+
+    /// Readable whenever a client is waiting to be accepted.
+    pub fn fd(&self) -> BorrowedFd<'_> {
+        self.listener.as_fd()
+    }
+
+    /// Answer all waiting clients with `handler`.
+    pub fn accept(&self, mut handler: impl FnMut(&str) -> Result<Vec<String>>) {
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return,
+                Err(err) => {
+                    warn!("failed to accept client: {err}");
+                    return;
+                }
+            };
+            if let Err(err) = serve(stream, &mut handler) {
+                warn!("failed to answer client: {err:#}");
+            }
+        }
+    }
+}
+
+fn serve(stream: UnixStream, handler: &mut impl FnMut(&str) -> Result<Vec<String>>) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+
+    let mut request = String::new();
+    BufReader::new(&stream).read_line(&mut request)?;
+    let request = request.trim();
+    debug!("client request: {request}");
+
+    let mut stream = stream;
+    match handler(request) {
+        Ok(lines) => {
+            for line in lines {
+                writeln!(stream, "{line}")?;
+            }
+        }
+        Err(err) => writeln!(stream, "{ERROR_PREFIX}{err:#}")?,
+    }
+    Ok(())
+}
+
+/// Send `request` to the daemon and return the lines it answered with.
+pub fn request(request: &str) -> Result<Vec<String>> {
+    let path = socket_path()?;
+    let mut stream = UnixStream::connect(&path).with_context(|| {
+        format!(
+            "could not connect to {}, is \"conductor daemon\" running?",
+            path.display()
+        )
+    })?;
+    writeln!(stream, "{request}")?;
+
+    let mut lines = Vec::new();
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if let Some(err) = line.strip_prefix(ERROR_PREFIX) {
+            bail!("daemon: {err}");
+        }
+        lines.push(line);
+    }
+    Ok(lines)
+}
@@ -1,28 +1,53 @@
 mod args;
+mod batch;
+mod command;
+mod complete;
 mod cosmic;
+mod daemon;
+mod desktop;
+mod doctor;
+mod error;
+mod event;
+mod focus;
+mod hooks;
+mod ipc;
+mod marks;
+mod notify;
 mod output;
+mod pick;
 mod print;
+mod record;
+mod request;
+mod scratchpad;
+mod selector;
+mod shell;
+mod shells;
+mod snapshot;
+mod state;
+mod status;
 mod toplevel;
+mod tui;
+mod undo;
+mod verify;
 mod workspace;
 
-use args::{Arguments, Command, ToplevelSubcommand, WorkspaceIdent, WorkspaceSubcommand};
-use clap::Parser;
-use cosmic::AppData;
-use cosmic_client_toolkit::{
-    sctk::{output::OutputState, registry::RegistryState, seat::SeatState},
-    toplevel_info::ToplevelInfoState,
-    toplevel_management::ToplevelManagerState,
-    workspace::WorkspaceState,
-};
-use log::{LevelFilter, debug, trace};
-use print::SaveDrop;
+use args::{Arguments, Command, OutputFormat};
+use clap::{CommandFactory, Parser, error::ErrorKind};
+use clap_complete::CompleteEnv;
+use command::RunOptions;
+use cosmic::Session;
+use log::LevelFilter;
+use print::Print;
 use simple_logger::SimpleLogger;
-use toplevel::SetStateAction;
-use wayland_client::{Connection, globals::registry_queue_init};
 
-use std::{cmp::min, io::Write, thread, time::Duration};
+use std::{io::Write, process::ExitCode};
+
+fn main() -> ExitCode {
+    CompleteEnv::with_factory(complete::command)
+        .var(shells::COMPLETE_VAR)
+        .shells(shells::SHELLS)
+        .complete();
 
-fn main() -> anyhow::Result<()> {
     SimpleLogger::new()
         .with_level(if cfg!(debug_assertions) {
             LevelFilter::Debug
@@ -34,137 +59,108 @@ fn main() -> anyhow::Result<()> {
         .unwrap();
 
     let args = Arguments::parse();
+    let format = args.format;
 
-    let connection: Connection = Connection::connect_to_env().unwrap();
-
-    let (globals, mut event_queue) = registry_queue_init(&connection).unwrap();
-    let qh = event_queue.handle();
-    let registry_state = RegistryState::new(&globals);
-
-    let mut app_data = AppData {
-        output_state: OutputState::new(&globals, &qh),
-        seat_state: SeatState::new(&globals, &qh),
-        workspace_state: WorkspaceState::new(&registry_state, &qh),
-        toplevel_info_state: ToplevelInfoState::new(&registry_state, &qh),
-        toplevel_manager_state: ToplevelManagerState::new(&registry_state, &qh),
-        registry_state,
-        toplevl_done: false,
-        workspace_done: false,
-        output_count: 0,
-    };
-
-    let check_done = |app_data: &AppData| {
-        app_data.toplevl_done && app_data.output_count > 0 && app_data.workspace_done
-    };
+    // rofi reads its own line format from stdout, which the printers can't produce
+    if matches!(args.command, Command::Pick { rofi: true, .. })
+        && !matches!(format, OutputFormat::Human)
+    {
+        Arguments::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!("the argument '--rofi' cannot be used with '--format {format}'"),
+            )
+            .exit();
+    }
 
-    let mut count = 1u64;
-    let mut delay = Duration::from_millis(20);
-    while !check_done(&app_data) {
-        if event_queue.roundtrip(&mut app_data).unwrap() == 0 {
-            thread::sleep(delay);
-            trace!("roundtrip sleep: {:?}", delay);
-            delay = min(delay * 2, Duration::from_millis(200));
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            match format {
+                OutputFormat::Human => eprintln!("Error: {err:?}"),
+                OutputFormat::Json | OutputFormat::JsonPretty => {
+                    eprintln!("{}", error::to_json(&err))
+                }
+            }
+            ExitCode::from(error::exit_code(&err))
         }
-        count += 1;
     }
-    debug!("finished {count} wayland event roundtrips");
+}
 
-    let mut stdout = std::io::stdout();
-    let mut json_buffer = String::new();
-    let mut printer = match args.format {
-        args::OutputFormat::Human => print::human(&mut stdout),
-        args::OutputFormat::Json | args::OutputFormat::JsonPretty => print::json(&mut json_buffer)?,
+fn run(args: Arguments) -> anyhow::Result<()> {
+    let options = RunOptions {
+        timeout: args.timeout,
+        verify: args.verify,
+        dry_run: args.dry_run,
     };
 
-    match args.command {
-        Command::Toplevels { subcommand } => match subcommand.unwrap_or_default() {
-            ToplevelSubcommand::List {
-                display,
-                workspace,
-                geometry,
-            } => toplevel::list(&app_data, &mut printer, workspace, display, geometry)?,
-            ToplevelSubcommand::Activate { id } => toplevel::activate(&app_data, &id)?,
-            ToplevelSubcommand::Max {
-                id,
-                unset: minimize,
-                toggle,
-            } => toplevel::maximize(&app_data, &id, SetStateAction::from(minimize, toggle)?)?,
-            ToplevelSubcommand::Min {
-                id,
-                unset: minimize,
-                toggle,
-            } => toplevel::minimize(&app_data, &id, SetStateAction::from(minimize, toggle)?)?,
-            ToplevelSubcommand::Fullscreen {
-                id,
-                minimize,
-                toggle,
-            } => toplevel::fullscreen(&app_data, &id, SetStateAction::from(minimize, toggle)?)?,
-            ToplevelSubcommand::Sticky {
-                id,
-                minimize,
-                toggle,
-            } => toplevel::sticky(&app_data, &id, SetStateAction::from(minimize, toggle)?)?,
-            ToplevelSubcommand::Move {
-                id,
-                workspace,
-                display,
-            } => toplevel::move_to(
-                &app_data,
-                &id,
-                WorkspaceIdent {
-                    name: workspace,
-                    display,
-                },
-            )?,
-        },
-        Command::Outputs => output::list(&app_data, &mut printer)?,
-        Command::WorkspaceGroups => workspace::list_groups(&app_data, &mut printer)?,
-        Command::Workspaces { subcommand } => match subcommand.unwrap_or_default() {
-            WorkspaceSubcommand::List { capabilities } => {
-                workspace::list(&app_data, &mut printer, capabilities)?
-            }
-            WorkspaceSubcommand::MoveToPos {
-                workspace,
-                position,
-            } => workspace::move_to(&app_data, workspace, position.into(), None)?,
-            WorkspaceSubcommand::MoveToDisplay {
-                workspace,
-                target_display,
-                position,
-            } => workspace::move_to(
-                &app_data,
-                workspace,
-                position.map(Into::into).unwrap_or(usize::MAX),
-                Some(&target_display),
-            )?,
-            WorkspaceSubcommand::Pin { workspace } => workspace::pin(&app_data, workspace, true)?,
-            WorkspaceSubcommand::Unpin { workspace } => {
-                workspace::pin(&app_data, workspace, false)?
-            }
-            WorkspaceSubcommand::Activate { workspace } => {
-                workspace::activate(&app_data, workspace)?
-            }
-        },
+    if let Some(path) = &args.from_snapshot {
+        let snapshot = snapshot::Snapshot::load(path)?;
+        return print::stdout(args.format, |printer| {
+            command::run_from_snapshot(&snapshot, args.command, printer)
+        });
     }
-    event_queue.flush().unwrap();
-
-    printer.save_drop()?;
-    drop(printer);
 
-    match args.format {
-        args::OutputFormat::Json => {
-            writeln!(stdout, "{json_buffer}")?;
+    match args.command {
+        Command::Snapshot => {
+            let session = Session::connect(options.timeout)?;
+            let snapshot = snapshot::Snapshot::new(&session.app_data);
+            println!("{}", serde_json::to_string_pretty(&snapshot)?);
+            return Ok(());
+        }
+        Command::Record { duration, file } => {
+            let mut session = Session::connect(options.timeout)?;
+            return record::record(&mut session, &file, duration);
+        }
+        Command::Shell => {
+            // the shell creates a printer for each command it runs
+            let mut session = Session::connect(options.timeout)?;
+            return shell::run(&mut session, &options, args.format);
+        }
+        Command::Tui => {
+            let mut session = Session::connect(options.timeout)?;
+            return tui::run(&mut session);
         }
-        args::OutputFormat::JsonPretty => {
-            // TODO not the most robust crate. I might want to use serde_json and serde_transcode
-            use json_pretty::PrettyFormatter;
-            let formatter = PrettyFormatter::from_string(&json_buffer);
-            writeln!(stdout, "{}", formatter.pretty())?;
+        Command::Status { bar } => {
+            let mut session = Session::connect(options.timeout)?;
+            return status::run(&mut session, bar);
         }
-        _ => {
-            // nothing to do, format prints directly to stdout
+        Command::Daemon {
+            hooks,
+            notify_urgent,
+        } => {
+            let hooks = hooks::load_or_default(hooks)?;
+            let mut session = Session::connect(options.timeout)?;
+            return daemon::run(
+                &mut session,
+                &daemon::Options {
+                    hooks,
+                    notify_urgent,
+                },
+            );
         }
+        _ => {}
+    }
+
+    print::stdout(args.format, |printer| {
+        execute(args.command, options, printer)
+    })
+}
+
+fn execute<W: Write>(
+    command: Command,
+    options: RunOptions,
+    printer: &mut impl Print<W>,
+) -> anyhow::Result<()> {
+    if let Command::Doctor = command {
+        // doctor needs to work even if required globals are missing
+        return doctor::report(printer, options.timeout);
+    }
+    if let Command::Replay { file, hooks } = command {
+        // replaying only needs the recording
+        return record::replay(&file, hooks, printer);
     }
 
-    Ok(())
+    let mut session = Session::connect(options.timeout)?;
+    command::run(&mut session, command, &options, printer)
 }
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    args::WorkspaceIdent,
+    cosmic::AppData,
+    error::Error,
+    selector::Selector,
+    state,
+    workspace::{self, get_workspace},
+};
+
+const STATE_NAME: &str = "marks";
+
+/// Marks are used in place of a toplevel id or workspace name with this prefix, e.g. "@editor".
+pub const PREFIX: char = '@';
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Mark {
+    Toplevel {
+        identifier: String,
+    },
+    Workspace {
+        name: String,
+        display: Option<String>,
+    },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Marks {
+    marks: BTreeMap<String, Mark>,
+}
+
+/// Mark the toplevel matching `selector`, replacing any mark with the same name.
+pub fn mark_toplevel(app_data: &AppData, selector: &str, name: &str) -> Result<()> {
+    let toplevels = Selector::parse(selector)?.select(app_data);
+    let toplevel = match toplevels.as_slice() {
+        [toplevel] => toplevel,
+        [] => bail!(Error::NotFound(format!(
+            "no toplevel matches \"{selector}\""
+        ))),
+        _ => bail!(Error::Ambiguous(format!(
+            "\"{selector}\" matches {} toplevels but a mark is for a single toplevel",
+            toplevels.len()
+        ))),
+    };
+    save(
+        app_data,
+        name,
+        Mark::Toplevel {
+            identifier: toplevel.identifier.clone(),
+        },
+    )
+}
+
+/// Mark the workspace, replacing any mark with the same name.
+///
+/// The workspace is remembered by name and display.
+pub fn mark_workspace(app_data: &AppData, workspace: &WorkspaceIdent, name: &str) -> Result<()> {
+    let (group, _, workspace) = get_workspace(app_data, workspace)?;
+    let display = workspace::group_display(app_data, group);
+    save(
+        app_data,
+        name,
+        Mark::Workspace {
+            name: workspace.name.clone(),
+            display,
+        },
+    )
+}
+
+fn save(app_data: &AppData, name: &str, mark: Mark) -> Result<()> {
+    let name = name.strip_prefix(PREFIX).unwrap_or(name);
+    if name.is_empty() {
+        bail!("the mark name can not be empty");
+    }
+    let mut marks: Marks = state::load(STATE_NAME)?;
+    marks.marks.insert(name.to_string(), mark);
+    state::save(app_data, STATE_NAME, &marks)
+}
+
+fn get(name: &str) -> Result<Mark> {
+    let mut marks: Marks = state::load(STATE_NAME)?;
+    let Some(mark) = marks.marks.remove(name) else {
+        bail!(Error::NotFound(format!("unknown mark {PREFIX}{name}")));
+    };
+    Ok(mark)
+}
+
+/// The identifier of the toplevel marked `name`, without the prefix.
+pub fn toplevel(name: &str) -> Result<String> {
+    match get(name)? {
+        Mark::Toplevel { identifier } => Ok(identifier),
+        Mark::Workspace { .. } => bail!(Error::NotFound(format!(
+            "mark {PREFIX}{name} is a workspace, not a toplevel"
+        ))),
+    }
+}
+
+/// The workspace marked `name`, without the prefix.
+pub fn workspace(name: &str) -> Result<WorkspaceIdent> {
+    match get(name)? {
+        Mark::Workspace { name, display } => Ok(WorkspaceIdent { name, display }),
+        Mark::Toplevel { .. } => bail!(Error::NotFound(format!(
+            "mark {PREFIX}{name} is a toplevel, not a workspace"
+        ))),
+    }
+}
+
+/// Remove the marks of the toplevel, called by the daemon when it closes.
+pub fn forget_toplevel(app_data: &AppData, identifier: &str) -> Result<()> {
+    let mut marks: Marks = state::load(STATE_NAME)?;
+    let before = marks.marks.len();
+    marks
+        .marks
+        .retain(|_, mark| !matches!(mark, Mark::Toplevel { identifier: id } if id == identifier));
+    if marks.marks.len() != before {
+        state::save(app_data, STATE_NAME, &marks)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use zbus::{blocking::Connection, proxy, zvariant::Value};
+
+use crate::event::{ToplevelSnapshot, WorkspaceSnapshot};
+
+/// The action freedesktop notification servers invoke when the notification is clicked.
+const DEFAULT_ACTION: &str = "default";
+
+/// How many notifications are remembered so they can be clicked.
+const MAX_PENDING: usize = 64;
+
+#[proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+
+    #[zbus(signal)]
+    fn action_invoked(&self, id: u32, action_key: String) -> zbus::Result<()>;
+}
+
+/// Sends notifications about urgent windows over the session bus.
+///
+/// Uses whatever bus `DBUS_SESSION_BUS_ADDRESS` points to, so it can be run
+/// against a private bus with `dbus-run-session`.
+pub struct Notifier {
+    proxy: NotificationsProxyBlocking<'static>,
+    /// toplevel identifier for the last [MAX_PENDING] notifications that were not clicked yet
+    pending: BTreeMap<u32, String>,
+    clicks: Receiver<u32>,
+}
+
+impl Notifier {
+    pub fn connect() -> Result<Self> {
+        let connection =
+            Connection::session().context("failed to connect to the D-Bus session bus")?;
+        let proxy = NotificationsProxyBlocking::new(&connection)?;
+
+        let (click_sender, clicks) = mpsc::channel();
+        let actions = proxy.receive_action_invoked()?;
+        thread::spawn(move || {
+            for signal in actions {
+                match signal.args() {
+                    Ok(args) if args.action_key() == DEFAULT_ACTION => {
+                        if click_sender.send(*args.id()).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(err) => warn!("invalid ActionInvoked signal: {err}"),
+                }
+            }
+        });
+
+        Ok(Self {
+            proxy,
+            pending: BTreeMap::new(),
+            clicks,
+        })
+    }
+
+    /// Notify that `toplevel` on `workspace` wants attention.
+    pub fn urgent(
+        &mut self,
+        workspace: &WorkspaceSnapshot,
+        toplevel: &ToplevelSnapshot,
+    ) -> Result<()> {
+        let summary = format!("{} needs attention", toplevel.app_id);
+        let body = format!("{} on workspace {}", toplevel.title, workspace.name);
+        let id = self.proxy.notify(
+            "cosmic-conductor",
+            0,
+            "",
+            &summary,
+            &body,
+            &[DEFAULT_ACTION, "Show"],
+            HashMap::new(),
+            -1,
+        )?;
+        debug!(
+            "sent notification {id} for toplevel {}",
+            toplevel.identifier
+        );
+        self.pending.insert(id, toplevel.identifier.clone());
+        if self.pending.len() > MAX_PENDING {
+            // notification ids increase, so this drops the oldest one
+            self.pending.pop_first();
+        }
+        Ok(())
+    }
+
+    /// The identifiers of all toplevels whose notification was clicked since the last call.
+    pub fn clicked(&mut self) -> Vec<String> {
+        self.clicks
+            .try_iter()
+            .filter_map(|id| self.pending.remove(&id))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    use zbus::{
+        blocking::connection, interface, object_server::SignalEmitter, zvariant::OwnedValue,
+    };
+
+    use super::*;
+
+    const PATH: &str = "/org/freedesktop/Notifications";
+
+    /// Stands in for the notification server and remembers summary and body of each notification.
+    struct Server {
+        sent: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    #[interface(name = "org.freedesktop.Notifications")]
+    impl Server {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &self,
+            _app_name: String,
+            _replaces_id: u32,
+            _app_icon: String,
+            summary: String,
+            body: String,
+            _actions: Vec<String>,
+            _hints: HashMap<String, OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            let mut sent = self.sent.lock().unwrap();
+            sent.push((summary, body));
+            sent.len() as u32
+        }
+
+        #[zbus(signal)]
+        async fn action_invoked(
+            emitter: &SignalEmitter<'_>,
+            id: u32,
+            action_key: &str,
+        ) -> zbus::Result<()>;
+    }
+
+    /// Run with `dbus-run-session -- cargo test -- --ignored`, the bus must not
+    /// have a notification server yet.
+    #[test]
+    #[ignore = "needs a private D-Bus session bus"]
+    fn notifies_and_activates_on_click() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let server = connection::Builder::session()
+            .unwrap()
+            .name("org.freedesktop.Notifications")
+            .unwrap()
+            .serve_at(PATH, Server { sent: sent.clone() })
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let mut notifier = Notifier::connect().unwrap();
+        let workspace = WorkspaceSnapshot {
+            name: "2".into(),
+            outputs: vec!["DP-1".into()],
+            active: false,
+            urgent: true,
+        };
+        let toplevel = ToplevelSnapshot {
+            identifier: "1a2b".into(),
+            title: "vim".into(),
+            app_id: "foot".into(),
+            workspaces: vec!["2".into()],
+            outputs: vec!["DP-1".into()],
+            activated: false,
+        };
+        notifier.urgent(&workspace, &toplevel).unwrap();
+        assert_eq!(
+            *sent.lock().unwrap(),
+            [(
+                "foot needs attention".to_string(),
+                "vim on workspace 2".to_string()
+            )]
+        );
+
+        let iface = server.object_server().interface::<_, Server>(PATH).unwrap();
+        zbus::block_on(Server::action_invoked(
+            iface.signal_emitter(),
+            1,
+            DEFAULT_ACTION,
+        ))
+        .unwrap();
+
+        // the click arrives on the thread listening for signals
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut clicked = notifier.clicked();
+        while clicked.is_empty() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            clicked = notifier.clicked();
+        }
+        assert_eq!(clicked, ["1a2b"]);
+    }
+}
@@ -8,6 +8,7 @@ use wayland_client::protocol::wl_output::WlOutput;
 use crate::{
     cosmic::AppData,
     print::{Print, PrintList},
+    snapshot::Snapshot,
 };
 
 pub fn display_name(output: &OutputInfo) -> String {
@@ -18,18 +19,11 @@ pub fn display_name(output: &OutputInfo) -> String {
     }
 }
 
-pub fn print_displays<'a, O: IntoIterator<Item = &'a WlOutput>, W: Write>(
-    app_data: &AppData,
+pub fn print_displays<'a, W: Write>(
     printer: &mut impl Print<W>,
-    outputs: O,
+    displays: impl Iterator<Item = &'a str>,
 ) -> Result<()> {
-    printer.inline_list(
-        "Displays",
-        outputs
-            .into_iter()
-            .filter_map(|handle| app_data.output_state.info(handle))
-            .map(|o| display_name(&o)),
-    )?;
+    printer.inline_list("Displays", displays)?;
     Ok(())
 }
 
@@ -46,30 +40,26 @@ pub fn find(app_data: &AppData, display: &str) -> Option<(WlOutput, OutputInfo)>
         .find(|(_, o)| display_name(o) == display)
 }
 
-pub fn list<W: Write>(app_data: &AppData, printer: &mut impl Print<W>) -> Result<()> {
+pub fn list<W: Write>(snapshot: &Snapshot, printer: &mut impl Print<W>) -> Result<()> {
     let mut printer = printer.sub_list("Outputs")?;
-    for output in app_data
-        .output_state
-        .outputs()
-        .filter_map(|o| app_data.output_state.info(&o))
-    {
+    for output in snapshot.outputs.iter() {
         let mut printer = printer.sub_struct()?;
         printer.optional("Name", output.name.as_ref())?;
         printer.optional("Description", output.description.as_ref())?;
 
-        if let Some(mode) = output.modes.iter().find(|m| m.current) {
-            printer.field("width", mode.dimensions.0)?;
-            printer.field("height", mode.dimensions.1)?;
-            printer.field("refresh", mode.refresh_rate)?;
+        if let Some(mode) = output.mode.as_ref() {
+            printer.field("width", mode.width)?;
+            printer.field("height", mode.height)?;
+            printer.field("refresh", mode.refresh)?;
             printer.field("preferred", mode.preferred)?;
         }
 
-        printer.field("x", output.location.0)?;
-        printer.field("y", output.location.1)?;
-        printer.field("Make", output.make)?;
-        printer.field("Model", output.model)?;
-        printer.field("phys width", output.physical_size.0)?;
-        printer.field("phys height", output.physical_size.1)?;
+        printer.field("x", output.x)?;
+        printer.field("y", output.y)?;
+        printer.field("Make", &output.make)?;
+        printer.field("Model", &output.model)?;
+        printer.field("phys width", output.physical_width)?;
+        printer.field("phys height", output.physical_height)?;
     }
 
     Ok(())
@@ -0,0 +1,130 @@
+use std::{
+    env,
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result, bail};
+use cosmic_client_toolkit::toplevel_info::ToplevelInfo;
+use log::debug;
+
+use crate::{
+    args::{PickAction, WorkspaceIdent},
+    cosmic::AppData,
+    error::Error,
+    toplevel,
+};
+
+/// Let the user select a toplevel with `launcher` and apply `action` to it.
+///
+/// The launcher gets one line per toplevel on stdin and is expected to print
+/// the selected line, like dmenu, fuzzel or `rofi -dmenu` do.
+/// Nothing happens if the launcher is cancelled.
+pub fn run(app_data: &AppData, launcher: &str, action: &PickAction) -> Result<()> {
+    let entries = entries(app_data);
+
+    let words = shlex::split(launcher).context("invalid quoting in launcher")?;
+    let Some((program, args)) = words.split_first() else {
+        bail!("launcher must not be empty");
+    };
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to start launcher {program}"))?;
+
+    {
+        let mut stdin = child.stdin.take().context("no stdin for launcher")?;
+        for (line, _) in entries.iter() {
+            writeln!(stdin, "{line}")?;
+        }
+        // stdin is closed here so the launcher knows that all entries were sent
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        debug!("launcher exited with {}, nothing selected", output.status);
+        return Ok(());
+    }
+    let selection = String::from_utf8_lossy(&output.stdout);
+    let selection = selection.trim_end_matches('\n');
+    if selection.is_empty() {
+        debug!("nothing selected");
+        return Ok(());
+    }
+
+    let Some((_, toplevel)) = entries.iter().find(|(line, _)| line == selection) else {
+        bail!(Error::NotFound(format!(
+            "launcher returned unknown toplevel: {selection}"
+        )));
+    };
+    apply(app_data, toplevel, action)
+}
+
+/// Act as a rofi script mode, e.g. `rofi -show conductor -modes "conductor:conductor pick --rofi"`.
+///
+/// Rofi calls the script once to get the entries and again with `ROFI_RETV=1`
+/// once an entry is selected. The toplevel identifier is passed through `ROFI_INFO`.
+pub fn rofi(app_data: &AppData, action: &PickAction) -> Result<()> {
+    match env::var("ROFI_RETV").as_deref() {
+        Err(_) | Ok("0") => {
+            let mut stdout = std::io::stdout();
+            writeln!(stdout, "\0no-custom\x1ftrue")?;
+            for (line, toplevel) in entries(app_data) {
+                writeln!(stdout, "{line}\0info\x1f{}", toplevel.identifier)?;
+            }
+            Ok(())
+        }
+        Ok("1") => {
+            let identifier =
+                env::var("ROFI_INFO").context("rofi did not pass the selected toplevel")?;
+            let toplevel = toplevel::find_from_id(app_data, &identifier)?;
+            apply(app_data, toplevel, action)
+        }
+        Ok(retv) => {
+            debug!("ignoring rofi return value {retv}");
+            Ok(())
+        }
+    }
+}
+
+fn apply(app_data: &AppData, toplevel: &ToplevelInfo, action: &PickAction) -> Result<()> {
+    match action {
+        PickAction::Activate => toplevel::activate(app_data, toplevel),
+        PickAction::Close => toplevel::close(app_data, toplevel),
+        PickAction::Move(workspace) => toplevel::move_to(
+            app_data,
+            toplevel,
+            &WorkspaceIdent {
+                name: workspace.clone(),
+                display: None,
+            },
+        ),
+    }
+}
+
+/// One line per toplevel in the form "title — app_id [workspace]".
+///
+/// Lines that would be identical get the identifier appended, so every
+/// selection maps back to exactly one toplevel.
+fn entries(app_data: &AppData) -> Vec<(String, &ToplevelInfo)> {
+    let mut entries: Vec<(String, &ToplevelInfo)> = Vec::new();
+    for toplevel in app_data.toplevel_info_state.toplevels() {
+        let workspaces: Vec<_> = toplevel
+            .workspace
+            .iter()
+            .filter_map(|w| app_data.workspace_state.workspace_info(w))
+            .map(|w| w.name.as_str())
+            .collect();
+        let mut line = format!("{} — {}", toplevel.title, toplevel.app_id);
+        if !workspaces.is_empty() {
+            line = format!("{line} [{}]", workspaces.join(", "));
+        }
+        if entries.iter().any(|(existing, _)| *existing == line) {
+            line = format!("{line} ({})", toplevel.identifier);
+        }
+        entries.push((line, toplevel));
+    }
+    entries
+}
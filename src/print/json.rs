@@ -4,6 +4,25 @@ use super::{Print, PrintList, SaveDrop};
 
 use anyhow::Result;
 
+/// Escape `value` so it can be used within a json string.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 pub struct Printer<'a, W: Write> {
     buffer: &'a mut String,
     first: bool,
@@ -50,7 +69,7 @@ impl<W: Write> SaveDrop for Printer<'_, W> {
 impl<W: Write> Print<W> for Printer<'_, W> {
     fn field<D: std::fmt::Display>(&mut self, name: &str, value: D) -> Result<()> {
         self.field(name)?;
-        write!(self.buffer, "\"{value}\"")?;
+        write!(self.buffer, "\"{}\"", escape(&value.to_string()))?;
         Ok(())
     }
 
@@ -105,7 +124,7 @@ impl<W: Write> ListPrinter<'_, W> {
 impl<W: Write> PrintList<W> for ListPrinter<'_, W> {
     fn item<D: std::fmt::Display>(&mut self, value: D) -> Result<()> {
         self.comma()?;
-        write!(self.buffer, "\"{value}\"")?;
+        write!(self.buffer, "\"{}\"", escape(&value.to_string()))?;
         Ok(())
     }
 
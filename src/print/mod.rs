@@ -1,12 +1,54 @@
 use anyhow::Result;
 use std::{
     fmt::{Debug, Display},
-    io::Write,
+    io::{Stdout, Write},
 };
 
+use crate::args::OutputFormat;
+
 pub mod human;
 pub mod json;
 
+/// Create a printer for `format` and pass it to `f`.
+///
+/// Json output is buffered and written to stdout once `f` returns,
+/// even if `f` fails.
+pub fn stdout<F>(format: OutputFormat, f: F) -> Result<()>
+where
+    F: FnOnce(&mut Printer<'_, Stdout>) -> Result<()>,
+{
+    let mut stdout = std::io::stdout();
+    let mut json_buffer = String::new();
+    let result = {
+        let mut printer = match format {
+            OutputFormat::Human => human(&mut stdout),
+            OutputFormat::Json | OutputFormat::JsonPretty => json(&mut json_buffer)?,
+        };
+
+        let result = f(&mut printer);
+
+        printer.save_drop()?;
+        result
+    };
+
+    match format {
+        OutputFormat::Json => {
+            writeln!(stdout, "{json_buffer}")?;
+        }
+        OutputFormat::JsonPretty => {
+            // TODO not the most robust crate. I might want to use serde_json and serde_transcode
+            use json_pretty::PrettyFormatter;
+            let formatter = PrettyFormatter::from_string(&json_buffer);
+            writeln!(stdout, "{}", formatter.pretty())?;
+        }
+        _ => {
+            // nothing to do, format prints directly to stdout
+        }
+    }
+
+    result
+}
+
 pub fn human<'a, W: Write + 'a>(writer: &'a mut W) -> Printer<'a, W> {
     Printer::Human(human::Printer::new(writer))
 }
@@ -0,0 +1,197 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, bail};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cosmic::Session,
+    daemon::Handler,
+    event::{self, ObservedChange, Tracker},
+    focus::History,
+    hooks,
+    print::{Print, PrintList},
+    snapshot::Snapshot,
+};
+
+/// How long to wait for events at once when recording without a duration.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The first line of a recording, the state the changes start from.
+#[derive(Debug, Serialize, Deserialize)]
+struct Start {
+    snapshot: Snapshot,
+    tracker: Tracker,
+}
+
+/// A line of a recording after the [Start].
+#[derive(Debug, Serialize, Deserialize)]
+struct Recorded {
+    /// milliseconds since the recording started
+    elapsed_ms: u64,
+    #[serde(flatten)]
+    change: ObservedChange,
+}
+
+/// Write the changes the daemon would react to into `path`, one json line each.
+///
+/// The changes are recorded as the wayland handlers report them, before they
+/// are turned into events, so replaying them exercises the same code.
+/// Records until `duration` passed or forever if it is `None`.
+pub fn record(session: &mut Session, path: &Path, duration: Option<Duration>) -> Result<()> {
+    let mut file =
+        File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    let start = Start {
+        snapshot: Snapshot::new(&session.app_data),
+        tracker: Tracker::new(&mut session.app_data),
+    };
+    writeln!(file, "{}", serde_json::to_string(&start)?)?;
+
+    let started = Instant::now();
+    debug!("recording changes to {}", path.display());
+
+    loop {
+        let timeout = match duration {
+            Some(duration) => {
+                let left = duration.saturating_sub(started.elapsed());
+                if left.is_zero() {
+                    return Ok(());
+                }
+                left.min(POLL_INTERVAL)
+            }
+            None => POLL_INTERVAL,
+        };
+        session.dispatch_timeout(timeout, &[])?;
+
+        for change in event::observe(&mut session.app_data) {
+            debug!("change: {change:?}");
+            let line = Recorded {
+                elapsed_ms: started.elapsed().as_millis() as u64,
+                change,
+            };
+            // written line by line, so stopping the recording never loses changes
+            writeln!(file, "{}", serde_json::to_string(&line)?)?;
+        }
+    }
+}
+
+/// Feed a recording through the daemon's event handling without a compositor.
+///
+/// The changes are turned into events and the hooks are run like in the
+/// daemon. Prints each event with the hooks it started, followed by the
+/// resulting focus history.
+pub fn replay<W: Write>(
+    path: &Path,
+    hooks: Option<PathBuf>,
+    printer: &mut impl Print<W>,
+) -> Result<()> {
+    let hooks = hooks::load_or_default(hooks)?;
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("failed to read recording {}", path.display()))?;
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+
+    let Some((_, first)) = lines.next() else {
+        bail!("the recording {} is empty", path.display());
+    };
+    let Start {
+        snapshot,
+        mut tracker,
+    } = serde_json::from_str(first)
+        .with_context(|| format!("{}:1: expected the start of a recording", path.display()))?;
+    let mut handler = Handler::new(&hooks, History::from_snapshot(&snapshot));
+
+    {
+        let mut printer = printer.sub_list("Events")?;
+        for (line_nr, line) in lines {
+            let recorded: Recorded = serde_json::from_str(line)
+                .with_context(|| format!("{}:{line_nr}: invalid change", path.display()))?;
+
+            for event in tracker.apply(vec![recorded.change]) {
+                let started = handler.handle(&event);
+                // one event at a time, so the hooks can't overtake each other
+                handler.wait();
+
+                let mut printer = printer.sub_struct()?;
+                printer.field("Time", format!("{}ms", recorded.elapsed_ms))?;
+                printer.field("Event", &event)?;
+                printer.inline_list("Hooks", started.iter().map(|hook| &hook.command))?;
+            }
+        }
+    }
+    printer.list("Focus History", handler.history().identifiers().iter())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::print;
+
+    #[test]
+    fn replay_runs_hooks_and_records_focus() {
+        let recording = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("recordings")
+            .join("focus-and-outputs.jsonl");
+        let dir = env::temp_dir().join(format!("conductor-replay-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("hooks.log");
+        let hooks = dir.join("hooks");
+        fs::write(
+            &hooks,
+            format!(
+                "on toplevel_focused run echo \"focused $CONDUCTOR_ID\" >> '{0}'\n\
+                 on output_added output=HDMI-A-1 run echo \"added $CONDUCTOR_OUTPUT\" >> '{0}'\n\
+                 on output_added output=DP-2 run echo never >> '{0}'\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+
+        let mut buffer = String::new();
+        {
+            let mut printer = print::json::<Vec<u8>>(&mut buffer).unwrap();
+            replay(&recording, Some(hooks), &mut printer).unwrap();
+        }
+        let output: Value = serde_json::from_str(&buffer).unwrap();
+
+        let events: Vec<_> = output["Events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|event| event["Event"].as_str()?.split(' ').next())
+            .collect();
+        assert_eq!(
+            events,
+            [
+                "toplevel_opened",
+                "toplevel_changed",
+                "toplevel_focused",
+                "toplevel_changed",
+                "workspace_urgent",
+                "output_added",
+                "toplevel_closed",
+            ]
+        );
+        assert_eq!(output["Focus History"], json!(["c3d4"]));
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            "focused c3d4\nadded HDMI-A-1\n"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
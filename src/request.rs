@@ -0,0 +1,256 @@
+use std::fmt::Display;
+
+use anyhow::{Context, Result, bail};
+use cosmic_client_toolkit::{toplevel_info::ToplevelInfo, workspace::Workspace};
+use cosmic_protocols::{
+    toplevel_info::v1::client::zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1,
+    toplevel_management::v1::client::zcosmic_toplevel_manager_v1::ZcosmicToplelevelManagementCapabilitiesV1 as ToplevelCapability,
+    workspace::v2::client::zcosmic_workspace_handle_v2::{
+        WorkspaceCapabilities, ZcosmicWorkspaceHandleV2,
+    },
+};
+use wayland_client::{
+    WEnum,
+    protocol::{wl_output::WlOutput, wl_seat::WlSeat},
+};
+use wayland_protocols::ext::workspace::v1::client::ext_workspace_handle_v1::{
+    ExtWorkspaceHandleV1, WorkspaceCapabilities as ExtWorkspaceCapabilities,
+};
+
+use crate::{cosmic::AppData, error::Error, undo};
+
+// aparently every value but 0 is ignored. Not sure what this means, but the current
+// cosmic-compositor just checks that it is 0. Events with other values are ignored.
+const AXIS: u32 = 0;
+
+/// A single protocol request conductor sends to the compositor.
+///
+/// All requests go through [AppData::send] which keeps a log of them.
+#[derive(Debug, Clone)]
+pub enum Request {
+    Toplevel {
+        identifier: String,
+        title: String,
+        handle: ZcosmicToplevelHandleV1,
+        action: ToplevelRequest,
+    },
+    Workspace {
+        name: String,
+        handle: ExtWorkspaceHandleV1,
+        action: WorkspaceRequest,
+    },
+    WorkspaceCommit,
+}
+
+#[derive(Debug, Clone)]
+pub enum ToplevelRequest {
+    Maximize(bool),
+    Minimize(bool),
+    Fullscreen(bool),
+    Sticky(bool),
+    Activate(WlSeat),
+    Close,
+    MoveToWorkspace {
+        workspace: ExtWorkspaceHandleV1,
+        workspace_name: String,
+        output: WlOutput,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum WorkspaceRequest {
+    Activate,
+    Pin {
+        cosmic_handle: ZcosmicWorkspaceHandleV2,
+        pin: bool,
+    },
+    Move {
+        cosmic_handle: ZcosmicWorkspaceHandleV2,
+        other: ExtWorkspaceHandleV1,
+        other_name: String,
+        after: bool,
+    },
+}
+
+impl Request {
+    pub fn toplevel(
+        toplevel: &ToplevelInfo,
+        handle: &ZcosmicToplevelHandleV1,
+        action: ToplevelRequest,
+    ) -> Self {
+        Request::Toplevel {
+            identifier: toplevel.identifier.clone(),
+            title: toplevel.title.clone(),
+            handle: handle.clone(),
+            action,
+        }
+    }
+
+    pub fn workspace(workspace: &Workspace, action: WorkspaceRequest) -> Self {
+        Request::Workspace {
+            name: workspace.name.clone(),
+            handle: workspace.handle.clone(),
+            action,
+        }
+    }
+
+    /// Whether the compositor advertised the capability the request needs.
+    fn is_allowed(&self, app_data: &AppData) -> bool {
+        match self {
+            Request::Toplevel { action, .. } => {
+                let capability = match action {
+                    ToplevelRequest::Maximize(_) => ToplevelCapability::Maximize,
+                    ToplevelRequest::Minimize(_) => ToplevelCapability::Minimize,
+                    ToplevelRequest::Fullscreen(_) => ToplevelCapability::Fullscreen,
+                    ToplevelRequest::Sticky(_) => ToplevelCapability::Sticky,
+                    ToplevelRequest::Activate(_) => ToplevelCapability::Activate,
+                    ToplevelRequest::Close => ToplevelCapability::Close,
+                    ToplevelRequest::MoveToWorkspace { .. } => ToplevelCapability::MoveToWorkspace,
+                };
+                app_data
+                    .toplevel_capabilities
+                    .contains(&WEnum::Value(capability))
+            }
+            Request::Workspace { handle, action, .. } => {
+                let Some(workspace) = app_data.workspace_state.workspace_info(handle) else {
+                    // gone already, which the compositor reports on its own
+                    return true;
+                };
+                match action {
+                    WorkspaceRequest::Activate => workspace
+                        .capabilities
+                        .contains(ExtWorkspaceCapabilities::Activate),
+                    WorkspaceRequest::Pin { .. } => workspace
+                        .cosmic_capabilities
+                        .contains(WorkspaceCapabilities::Pin),
+                    WorkspaceRequest::Move { .. } => workspace
+                        .cosmic_capabilities
+                        .contains(WorkspaceCapabilities::Move),
+                }
+            }
+            Request::WorkspaceCommit => true,
+        }
+    }
+
+    fn send(&self, app_data: &AppData) -> Result<()> {
+        match self {
+            Request::Toplevel { handle, action, .. } => {
+                let manager = &app_data.toplevel_manager_state.manager;
+                match action {
+                    ToplevelRequest::Maximize(true) => manager.set_maximized(handle),
+                    ToplevelRequest::Maximize(false) => manager.unset_maximized(handle),
+                    ToplevelRequest::Minimize(true) => manager.set_minimized(handle),
+                    ToplevelRequest::Minimize(false) => manager.unset_minimized(handle),
+                    ToplevelRequest::Fullscreen(true) => manager.set_fullscreen(handle, None),
+                    ToplevelRequest::Fullscreen(false) => manager.unset_fullscreen(handle),
+                    ToplevelRequest::Sticky(true) => manager.set_sticky(handle),
+                    ToplevelRequest::Sticky(false) => manager.unset_sticky(handle),
+                    ToplevelRequest::Activate(seat) => manager.activate(handle, seat),
+                    ToplevelRequest::Close => manager.close(handle),
+                    ToplevelRequest::MoveToWorkspace {
+                        workspace, output, ..
+                    } => manager.move_to_ext_workspace(handle, workspace, output),
+                }
+            }
+            Request::Workspace { handle, action, .. } => match action {
+                WorkspaceRequest::Activate => handle.activate(),
+                WorkspaceRequest::Pin {
+                    cosmic_handle,
+                    pin: true,
+                } => cosmic_handle.pin(),
+                WorkspaceRequest::Pin {
+                    cosmic_handle,
+                    pin: false,
+                } => cosmic_handle.unpin(),
+                WorkspaceRequest::Move {
+                    cosmic_handle,
+                    other,
+                    after: true,
+                    ..
+                } => cosmic_handle.move_after(other, AXIS),
+                WorkspaceRequest::Move {
+                    cosmic_handle,
+                    other,
+                    after: false,
+                    ..
+                } => cosmic_handle.move_before(other, AXIS),
+            },
+            Request::WorkspaceCommit => app_data
+                .workspace_state
+                .workspace_manager()
+                .get()
+                .context("could not get acccess to workspace manager")?
+                .commit(),
+        }
+        Ok(())
+    }
+}
+
+impl AppData {
+    /// Send `request` to the compositor and record it in [AppData::requests].
+    ///
+    /// In a dry run the request is only recorded. Fails with
+    /// [Error::CapabilityMissing] if the compositor does not allow it, even in a dry run.
+    pub fn send(&self, request: Request) -> Result<()> {
+        if !request.is_allowed(self) {
+            bail!(Error::CapabilityMissing(format!(
+                "the compositor lacks the capability to {request}"
+            )));
+        }
+        if let Some(inverses) = self.inverses.borrow_mut().as_mut() {
+            for inverse in undo::inverses(&request, self) {
+                // the state hasn't changed yet, so the first inverse per target is the one to keep
+                if !inverses.contains(&inverse) {
+                    inverses.push(inverse);
+                }
+            }
+        }
+        if !self.dry_run {
+            request.send(self)?;
+        }
+        self.requests.borrow_mut().push(request);
+        Ok(())
+    }
+}
+
+impl Display for Request {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let set = |set: &bool| if *set { "set" } else { "unset" };
+        match self {
+            Request::Toplevel {
+                identifier,
+                title,
+                action,
+                ..
+            } => {
+                let toplevel = format!("toplevel \"{title}\" ({identifier})");
+                match action {
+                    ToplevelRequest::Maximize(s) => write!(f, "{} maximized on {toplevel}", set(s)),
+                    ToplevelRequest::Minimize(s) => write!(f, "{} minimized on {toplevel}", set(s)),
+                    ToplevelRequest::Fullscreen(s) => {
+                        write!(f, "{} fullscreen on {toplevel}", set(s))
+                    }
+                    ToplevelRequest::Sticky(s) => write!(f, "{} sticky on {toplevel}", set(s)),
+                    ToplevelRequest::Activate(_) => write!(f, "activate {toplevel}"),
+                    ToplevelRequest::Close => write!(f, "close {toplevel}"),
+                    ToplevelRequest::MoveToWorkspace { workspace_name, .. } => {
+                        write!(f, "move {toplevel} to workspace {workspace_name}")
+                    }
+                }
+            }
+            Request::Workspace { name, action, .. } => match action {
+                WorkspaceRequest::Activate => write!(f, "activate workspace {name}"),
+                WorkspaceRequest::Pin { pin: true, .. } => write!(f, "pin workspace {name}"),
+                WorkspaceRequest::Pin { pin: false, .. } => write!(f, "unpin workspace {name}"),
+                WorkspaceRequest::Move {
+                    other_name, after, ..
+                } => write!(
+                    f,
+                    "move workspace {name} {} workspace {other_name}",
+                    if *after { "after" } else { "before" }
+                ),
+            },
+            Request::WorkspaceCommit => write!(f, "commit workspace changes"),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, bail};
+use cosmic_client_toolkit::toplevel_info::ToplevelInfo;
+use cosmic_protocols::toplevel_info::v1::client::zcosmic_toplevel_handle_v1::State;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cosmic::AppData,
+    error::Error,
+    selector::Selector,
+    state,
+    toplevel::{self, SetStateAction},
+};
+
+const STATE_NAME: &str = "scratchpad";
+
+/// The scratchpad name of each toplevel by identifier.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Scratchpad {
+    toplevels: BTreeMap<String, String>,
+}
+
+/// Put the toplevels matching `selector` on the scratchpad, minimized and sticky.
+///
+/// `name` defaults to the app id of each toplevel.
+pub fn send(app_data: &AppData, selector: &str, name: Option<&str>) -> Result<()> {
+    let toplevels = Selector::parse(selector)?.select(app_data);
+    if toplevels.is_empty() {
+        bail!(Error::NotFound(format!(
+            "no toplevel matches \"{selector}\""
+        )));
+    }
+
+    let mut scratchpad: Scratchpad = state::load(STATE_NAME)?;
+    for toplevel in toplevels {
+        toplevel::sticky(app_data, toplevel, SetStateAction::Set)?;
+        toplevel::minimize(app_data, toplevel, SetStateAction::Set)?;
+        let name = name.unwrap_or(toplevel.app_id.as_str());
+        scratchpad
+            .toplevels
+            .insert(toplevel.identifier.clone(), name.to_string());
+    }
+    state::save(app_data, STATE_NAME, &scratchpad)
+}
+
+/// Hide the visible scratchpad toplevels with `name` or show one of them on
+/// the current workspace if none is visible.
+pub fn show(app_data: &AppData, name: Option<&str>) -> Result<()> {
+    let mut scratchpad: Scratchpad = state::load(STATE_NAME)?;
+    // toplevels that closed while the daemon was not running
+    let before = scratchpad.toplevels.len();
+    scratchpad
+        .toplevels
+        .retain(|id, _| find(app_data, id).is_some());
+    if scratchpad.toplevels.len() != before {
+        state::save(app_data, STATE_NAME, &scratchpad)?;
+    }
+
+    let toplevels: Vec<&ToplevelInfo> = scratchpad
+        .toplevels
+        .iter()
+        .filter(|(_, n)| name.is_none_or(|name| *n == name))
+        .filter_map(|(id, _)| find(app_data, id))
+        .collect();
+    let Some(first) = toplevels.first() else {
+        match name {
+            Some(name) => bail!(Error::NotFound(format!(
+                "no toplevel named \"{name}\" on the scratchpad"
+            ))),
+            None => bail!("the scratchpad is empty"),
+        }
+    };
+
+    let visible: Vec<_> = toplevels
+        .iter()
+        .filter(|t| !t.state.contains(&State::Minimized))
+        .collect();
+    if visible.is_empty() {
+        return toplevel::summon(app_data, first);
+    }
+    for toplevel in visible {
+        toplevel::minimize(app_data, toplevel, SetStateAction::Set)?;
+    }
+    Ok(())
+}
+
+/// Remove the toplevel from the scratchpad, called by the daemon when it closes.
+pub fn forget(app_data: &AppData, identifier: &str) -> Result<()> {
+    let mut scratchpad: Scratchpad = state::load(STATE_NAME)?;
+    if scratchpad.toplevels.remove(identifier).is_some() {
+        state::save(app_data, STATE_NAME, &scratchpad)?;
+    }
+    Ok(())
+}
+
+fn find<'a>(app_data: &'a AppData, identifier: &str) -> Option<&'a ToplevelInfo> {
+    app_data
+        .toplevel_info_state
+        .toplevels()
+        .find(|t| t.identifier == identifier)
+}
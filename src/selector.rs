@@ -0,0 +1,222 @@
+use std::io::Write;
+
+use anyhow::{Context, Result, bail};
+use cosmic_client_toolkit::toplevel_info::ToplevelInfo;
+
+use crate::{
+    args::{EachAction, WorkspaceIdent},
+    cosmic::AppData,
+    error::Error,
+    event::ToplevelSnapshot,
+    marks,
+    print::{Print, PrintList},
+    toplevel::{self, SetStateAction},
+};
+
+/// Keys that can be used in a selector.
+const KEYS: &[&str] = &["id", "title", "app_id", "workspace", "output"];
+
+/// Selects any number of toplevels.
+///
+/// Written as space separated `key=value` filters that all have to match, e.g.
+/// `"app_id=foot workspace=3"`. A word without "=" is the start of an id and
+/// `all` selects every toplevel. Marks can be used for the id and the workspace.
+#[derive(Debug, Clone)]
+pub struct Selector {
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone)]
+enum Filter {
+    /// `key=value` for every key but the workspace
+    Value(String, String),
+    /// a workspace name, with the display if it comes from a mark
+    Workspace(WorkspaceIdent),
+}
+
+impl Selector {
+    pub fn parse(selector: &str) -> Result<Self> {
+        let words = shlex::split(selector).context("invalid quoting in selector")?;
+        if words.is_empty() {
+            bail!("empty selector, use \"all\" to select every toplevel");
+        }
+        let filters = words
+            .into_iter()
+            .filter(|word| word != "all")
+            .map(|word| {
+                let Some((key, value)) = word.split_once('=') else {
+                    return Ok(Filter::Value("id".to_string(), resolve_id(&word)?));
+                };
+                if !KEYS.contains(&key) {
+                    bail!(
+                        "unknown selector key \"{key}\", expected one of {}",
+                        KEYS.join(", ")
+                    );
+                }
+                let value = match key {
+                    "id" => resolve_id(value)?,
+                    "workspace" => {
+                        return Ok(Filter::Workspace(match value.strip_prefix(marks::PREFIX) {
+                            Some(mark) => marks::workspace(mark)?,
+                            None => WorkspaceIdent {
+                                name: value.to_string(),
+                                display: None,
+                            },
+                        }));
+                    }
+                    _ => value.to_string(),
+                };
+                Ok(Filter::Value(key.to_string(), value))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { filters })
+    }
+
+    pub fn matches(&self, toplevel: &ToplevelSnapshot) -> bool {
+        self.filters.iter().all(|filter| match filter {
+            Filter::Value(key, expected) => match key.as_str() {
+                "id" => toplevel.identifier.starts_with(expected.as_str()),
+                "title" => toplevel.title == *expected,
+                "app_id" => toplevel.app_id == *expected,
+                "output" => toplevel.outputs.contains(expected),
+                _ => false,
+            },
+            // workspace names repeat on every display
+            Filter::Workspace(workspace) => {
+                toplevel.workspaces.contains(&workspace.name)
+                    && workspace
+                        .display
+                        .as_ref()
+                        .is_none_or(|display| toplevel.outputs.contains(display))
+            }
+        })
+    }
+
+    /// All toplevels that match, in the order of the compositor.
+    pub fn select<'a>(&self, app_data: &'a AppData) -> Vec<&'a ToplevelInfo> {
+        app_data
+            .toplevel_info_state
+            .toplevels()
+            .filter(|t| self.matches(&ToplevelSnapshot::new(app_data, t)))
+            .collect()
+    }
+}
+
+fn resolve_id(id: &str) -> Result<String> {
+    match id.strip_prefix(marks::PREFIX) {
+        Some(mark) => marks::toplevel(mark),
+        None => Ok(id.to_string()),
+    }
+}
+
+/// Apply `action` to every toplevel matching `selector` and print the result for each.
+///
+/// A failure doesn't stop the remaining toplevels. Returns how many failed.
+pub fn each<W: Write>(
+    app_data: &AppData,
+    printer: &mut impl Print<W>,
+    selector: &str,
+    action: &EachAction,
+) -> Result<usize> {
+    let toplevels = Selector::parse(selector)?.select(app_data);
+    if toplevels.is_empty() {
+        bail!(Error::NotFound(format!(
+            "no toplevel matches \"{selector}\""
+        )));
+    }
+
+    let mut failed = 0;
+    let mut printer = printer.sub_list("Toplevels")?;
+    for toplevel in toplevels {
+        let mut printer = printer.sub_struct()?;
+        printer.field("Title", &toplevel.title)?;
+        printer.field("Unique Identifier", &toplevel.identifier)?;
+        match apply(app_data, toplevel, action) {
+            Ok(()) => printer.field("Result", "ok")?,
+            Err(err) => {
+                failed += 1;
+                printer.field("Result", "failed")?;
+                printer.field("Error", format!("{err:#}"))?;
+            }
+        }
+    }
+    Ok(failed)
+}
+
+fn apply(app_data: &AppData, toplevel: &ToplevelInfo, action: &EachAction) -> Result<()> {
+    match action {
+        EachAction::Max { unset, toggle } => {
+            toplevel::maximize(app_data, toplevel, SetStateAction::from(*unset, *toggle)?)
+        }
+        EachAction::Min { unset, toggle } => {
+            toplevel::minimize(app_data, toplevel, SetStateAction::from(*unset, *toggle)?)
+        }
+        EachAction::Sticky { unset, toggle } => {
+            toplevel::sticky(app_data, toplevel, SetStateAction::from(*unset, *toggle)?)
+        }
+        EachAction::Close => toplevel::close(app_data, toplevel),
+        EachAction::Move { workspace, display } => toplevel::move_to(
+            app_data,
+            toplevel,
+            &WorkspaceIdent {
+                name: workspace.clone(),
+                display: display.clone(),
+            },
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toplevel() -> ToplevelSnapshot {
+        ToplevelSnapshot {
+            identifier: "a1b2c3".to_string(),
+            title: "my notes".to_string(),
+            app_id: "foot".to_string(),
+            workspaces: vec!["2".to_string()],
+            outputs: vec!["DP-1".to_string()],
+            activated: false,
+        }
+    }
+
+    fn matches(selector: &str) -> bool {
+        Selector::parse(selector).unwrap().matches(&toplevel())
+    }
+
+    #[test]
+    fn all_filters_have_to_match() {
+        assert!(matches("all"));
+        assert!(matches("app_id=foot workspace=2 output=DP-1"));
+        assert!(!matches("app_id=foot workspace=3"));
+        assert!(matches("'title=my notes'"));
+        assert!(!matches("title=my"));
+    }
+
+    #[test]
+    fn a_bare_word_is_an_id_prefix() {
+        assert!(matches("a1b"));
+        assert!(matches("id=a1b2c3"));
+        assert!(!matches("b2"));
+    }
+
+    #[test]
+    fn a_workspace_mark_keeps_its_display() {
+        let selector = |display: &str| Selector {
+            filters: vec![Filter::Workspace(WorkspaceIdent {
+                name: "2".to_string(),
+                display: Some(display.to_string()),
+            })],
+        };
+        assert!(selector("DP-1").matches(&toplevel()));
+        assert!(!selector("DP-2").matches(&toplevel()));
+    }
+
+    #[test]
+    fn invalid_selectors() {
+        assert!(Selector::parse("").is_err());
+        assert!(Selector::parse("'unclosed").is_err());
+        assert!(Selector::parse("class=foot").is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::Result;
+use clap::{CommandFactory, Parser};
+use log::debug;
+use rustyline::{
+    Context, Editor, Helper, Highlighter, Hinter, Validator,
+    completion::{Completer, Pair},
+    error::ReadlineError,
+    history::DefaultHistory,
+};
+
+use crate::{
+    args::{Command, CommandLine, OutputFormat},
+    command::{self, RunOptions},
+    complete::{self, Candidate},
+    cosmic::{AppData, Session},
+    print,
+};
+
+const PROMPT: &str = "conductor> ";
+
+/// Run an interactive shell until the user exits with "exit" or Ctrl-D.
+pub fn run(session: &mut Session, options: &RunOptions, format: OutputFormat) -> Result<()> {
+    let mut editor = Editor::<ShellHelper, DefaultHistory>::new()?;
+    editor.set_helper(Some(ShellHelper::default()));
+
+    loop {
+        // only for the completions, the command gets the state after the line is read
+        session.refresh()?;
+        if let Some(helper) = editor.helper_mut() {
+            helper.update(&session.app_data);
+        }
+
+        let line = match editor.readline(PROMPT) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+        if line == "exit" || line == "quit" {
+            break;
+        }
+
+        let Some(words) = shlex::split(line) else {
+            eprintln!("Error: invalid quoting");
+            continue;
+        };
+        let command = match CommandLine::try_parse_from(words) {
+            Ok(command_line) => command_line.command,
+            Err(err) => {
+                err.print()?;
+                continue;
+            }
+        };
+        if let Command::Shell = command {
+            eprintln!("Error: already in a shell");
+            continue;
+        }
+
+        session.refresh()?;
+        let result = print::stdout(format, |printer| {
+            command::run(session, command, options, printer)
+        });
+        if let Err(err) = result {
+            eprintln!("Error: {err:?}");
+        }
+    }
+    debug!("shell exited");
+
+    Ok(())
+}
+
+#[derive(Default, Helper, Hinter, Highlighter, Validator)]
+struct ShellHelper {
+    toplevels: Vec<Candidate>,
+    workspaces: Vec<Candidate>,
+    displays: Vec<Candidate>,
+}
+
+impl ShellHelper {
+    fn update(&mut self, app_data: &AppData) {
+        self.toplevels = complete::toplevels(app_data);
+        self.workspaces = complete::workspaces(app_data);
+        self.displays = complete::displays(app_data);
+    }
+
+    /// Candidates for the word following `previous`.
+    fn candidates(&self, previous: &[&str]) -> Vec<Candidate> {
+        if let Some(&"-d" | &"--display") = previous.last() {
+            return self.displays.clone();
+        }
+
+        let mut command = CommandLine::command();
+        let mut root = None;
+        for word in previous {
+            let Some(sub) = command.find_subcommand(word).cloned() else {
+                continue;
+            };
+            root.get_or_insert_with(|| sub.get_name().to_string());
+            command = sub;
+        }
+
+        if command.has_subcommands() {
+            return command
+                .get_subcommands()
+                .map(|sub| Candidate {
+                    value: sub.get_name().to_string(),
+                    help: sub.get_about().map(|about| about.to_string()),
+                })
+                .collect();
+        }
+
+        match root.as_deref() {
+            Some("toplevels") => [&self.toplevels[..], &self.workspaces[..]].concat(),
+            Some("workspaces") => [&self.workspaces[..], &self.displays[..]].concat(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+        let word = &line[start..];
+        let previous: Vec<&str> = line[..start].split_whitespace().collect();
+
+        let pairs = self
+            .candidates(&previous)
+            .into_iter()
+            .filter(|c| c.value.starts_with(word))
+            .map(|c| Pair {
+                display: match &c.help {
+                    Some(help) if !help.is_empty() => format!("{}  ({help})", c.value),
+                    _ => c.value.clone(),
+                },
+                replacement: shlex::try_quote(&c.value)
+                    .map(|quoted| quoted.into_owned())
+                    .unwrap_or(c.value),
+            })
+            .collect();
+
+        Ok((start, pairs))
+    }
+}
@@ -0,0 +1,67 @@
+use std::{borrow::Cow, ffi::OsString, io::Write};
+
+use clap_complete::env::{Bash, Elvish, EnvCompleter, Fish, Shells, Zsh};
+
+/// Environment variable that switches conductor into completion mode.
+pub const COMPLETE_VAR: &str = "COMPLETE";
+
+/// All shells supported for dynamic completions.
+pub const SHELLS: Shells<'static> = Shells(&[&Bash, &Elvish, &Fish, &Zsh, &Nushell]);
+
+/// Nushell adapter for clap's dynamic completion engine.
+///
+/// The registration installs an external completer that only handles
+/// conductor and therefore replaces any other external completer.
+#[derive(Debug, Clone, Copy)]
+pub struct Nushell;
+
+impl EnvCompleter for Nushell {
+    fn name(&self) -> &'static str {
+        "nu"
+    }
+
+    fn is(&self, name: &str) -> bool {
+        name == "nu" || name == "nushell"
+    }
+
+    fn write_registration(
+        &self,
+        var: &str,
+        name: &str,
+        bin: &str,
+        completer: &str,
+        buf: &mut dyn Write,
+    ) -> Result<(), std::io::Error> {
+        let completer = shlex::try_quote(completer).unwrap_or(Cow::Borrowed(completer));
+        writeln!(
+            buf,
+            r#"$env.config.completions.external.enable = true
+$env.config.completions.external.completer = {{|spans|
+    if ($spans | first) in ["{bin}" "{name}"] {{
+        {var}=nu ^{completer} -- ...$spans | lines | split column "\t" value description
+    }}
+}}"#
+        )
+    }
+
+    fn write_complete(
+        &self,
+        cmd: &mut clap::Command,
+        args: Vec<OsString>,
+        current_dir: Option<&std::path::Path>,
+        buf: &mut dyn Write,
+    ) -> Result<(), std::io::Error> {
+        let index = args.len() - 1;
+        let completions = clap_complete::engine::complete(cmd, args, index, current_dir)?;
+
+        for candidate in completions {
+            write!(buf, "{}", candidate.get_value().to_string_lossy())?;
+            if let Some(help) = candidate.get_help() {
+                let help = help.to_string();
+                write!(buf, "\t{}", help.lines().next().unwrap_or_default())?;
+            }
+            writeln!(buf)?;
+        }
+        Ok(())
+    }
+}
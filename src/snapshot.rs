@@ -0,0 +1,327 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+use cosmic_protocols::{
+    toplevel_info::v1::client::zcosmic_toplevel_handle_v1::State,
+    workspace::v2::client::zcosmic_workspace_handle_v2::{
+        State as CosmicWorkspaceState, WorkspaceCapabilities,
+    },
+};
+use serde::{Deserialize, Serialize};
+use wayland_client::protocol::wl_output::WlOutput;
+use wayland_protocols::ext::workspace::v1::client::{
+    ext_workspace_group_handle_v1::GroupCapabilities,
+    ext_workspace_handle_v1::{
+        ExtWorkspaceHandleV1, State as WorkspaceState,
+        WorkspaceCapabilities as ExtWorkspaceCapabilities,
+    },
+};
+
+use crate::{args::WorkspaceIdent, cosmic::AppData, error::Error, marks, output, workspace};
+
+/// Everything conductor knows about the compositor, with the wayland handles
+/// replaced by indices into the lists.
+///
+/// The list commands print from a snapshot, so they work the same on a live
+/// session and on a snapshot file from `conductor snapshot`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub outputs: Vec<Output>,
+    pub groups: Vec<Group>,
+    pub workspaces: Vec<Workspace>,
+    pub toplevels: Vec<Toplevel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Output {
+    /// the name used for `--display`
+    pub display: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub make: String,
+    pub model: String,
+    pub x: i32,
+    pub y: i32,
+    pub physical_width: i32,
+    pub physical_height: i32,
+    pub logical_size: Option<(i32, i32)>,
+    pub mode: Option<Mode>,
+}
+
+/// The current mode of an output.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mode {
+    pub width: i32,
+    pub height: i32,
+    pub refresh: i32,
+    pub preferred: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Group {
+    pub outputs: Vec<usize>,
+    /// in order
+    pub workspaces: Vec<usize>,
+    pub can_create_workspace: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workspace {
+    pub name: String,
+    pub wayland_id: Option<String>,
+    pub groups: Vec<usize>,
+    pub tiling: bool,
+    pub active: bool,
+    pub hidden: bool,
+    pub urgent: bool,
+    pub pinned: bool,
+    pub capabilities: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Toplevel {
+    pub identifier: String,
+    pub title: String,
+    pub app_id: String,
+    pub states: Vec<String>,
+    pub workspaces: Vec<usize>,
+    pub outputs: Vec<usize>,
+    /// the geometry on each output
+    pub geometry: Vec<(usize, Geometry)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Geometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Snapshot {
+    pub fn new(app_data: &AppData) -> Self {
+        // outputs without info are left out, so the indices have to skip them as well
+        let output_infos: Vec<_> = app_data
+            .output_state
+            .outputs()
+            .filter_map(|handle| Some((app_data.output_state.info(&handle)?, handle)))
+            .collect();
+        let output_index = |handle: &WlOutput| output_infos.iter().position(|(_, o)| o == handle);
+        let workspace_index = |handle: &ExtWorkspaceHandleV1| workspace_index(app_data, handle);
+
+        let outputs = output_infos
+            .iter()
+            .map(|(info, _)| Output {
+                display: output::display_name(info),
+                name: info.name.clone(),
+                description: info.description.clone(),
+                make: info.make.clone(),
+                model: info.model.clone(),
+                x: info.location.0,
+                y: info.location.1,
+                physical_width: info.physical_size.0,
+                physical_height: info.physical_size.1,
+                logical_size: info.logical_size,
+                mode: info.modes.iter().find(|m| m.current).map(|mode| Mode {
+                    width: mode.dimensions.0,
+                    height: mode.dimensions.1,
+                    refresh: mode.refresh_rate,
+                    preferred: mode.preferred,
+                }),
+            })
+            .collect();
+
+        let group_handles: Vec<_> = app_data.workspace_state.workspace_groups().collect();
+        let groups = group_handles
+            .iter()
+            .map(|group| Group {
+                outputs: group.outputs.iter().filter_map(output_index).collect(),
+                workspaces: group
+                    .workspaces
+                    .iter()
+                    .filter_map(workspace_index)
+                    .collect(),
+                can_create_workspace: group
+                    .capabilities
+                    .contains(GroupCapabilities::CreateWorkspace),
+            })
+            .collect();
+
+        let workspaces = app_data
+            .workspace_state
+            .workspaces()
+            .map(|w| Workspace {
+                name: w.name.clone(),
+                wayland_id: w.id.clone(),
+                groups: group_handles
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, group)| group.workspaces.contains(&w.handle))
+                    .map(|(i, _)| i)
+                    .collect(),
+                tiling: workspace::is_workspace_tiling(w),
+                active: w.state.contains(WorkspaceState::Active),
+                hidden: w.state.contains(WorkspaceState::Hidden),
+                urgent: w.state.contains(WorkspaceState::Urgent),
+                pinned: w.cosmic_state.contains(CosmicWorkspaceState::Pinned),
+                capabilities: [
+                    (
+                        "move",
+                        w.cosmic_capabilities.contains(WorkspaceCapabilities::Move),
+                    ),
+                    (
+                        "pin",
+                        w.cosmic_capabilities.contains(WorkspaceCapabilities::Pin),
+                    ),
+                    (
+                        "rename",
+                        w.cosmic_capabilities
+                            .contains(WorkspaceCapabilities::Rename),
+                    ),
+                    (
+                        "set tiling",
+                        w.cosmic_capabilities
+                            .contains(WorkspaceCapabilities::SetTilingState),
+                    ),
+                    (
+                        "activate",
+                        w.capabilities.contains(ExtWorkspaceCapabilities::Activate),
+                    ),
+                    (
+                        "assign",
+                        w.capabilities.contains(ExtWorkspaceCapabilities::Assign),
+                    ),
+                    (
+                        "deactivate",
+                        w.capabilities
+                            .contains(ExtWorkspaceCapabilities::Deactivate),
+                    ),
+                    (
+                        "remove",
+                        w.capabilities.contains(ExtWorkspaceCapabilities::Remove),
+                    ),
+                ]
+                .into_iter()
+                .filter(|(_, supported)| *supported)
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            })
+            .collect();
+
+        let toplevels = app_data
+            .toplevel_info_state
+            .toplevels()
+            .map(|t| Toplevel {
+                identifier: t.identifier.clone(),
+                title: t.title.clone(),
+                app_id: t.app_id.clone(),
+                states: t.state.iter().map(|s| format!("{s:?}")).collect(),
+                workspaces: t.workspace.iter().filter_map(workspace_index).collect(),
+                outputs: t.output.iter().filter_map(output_index).collect(),
+                geometry: t
+                    .geometry
+                    .iter()
+                    .filter_map(|(output, geometry)| {
+                        let geometry = Geometry {
+                            x: geometry.x,
+                            y: geometry.y,
+                            width: geometry.width,
+                            height: geometry.height,
+                        };
+                        output_index(output).map(|i| (i, geometry))
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Self {
+            outputs,
+            groups,
+            workspaces,
+            toplevels,
+        }
+    }
+
+    /// Read a snapshot written by `conductor snapshot`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read snapshot {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("invalid snapshot {}", path.display()))
+    }
+
+    /// The display names of `outputs`.
+    pub fn displays<'a>(&'a self, outputs: &'a [usize]) -> impl Iterator<Item = &'a str> {
+        outputs
+            .iter()
+            .filter_map(|i| self.outputs.get(*i))
+            .map(|o| o.display.as_str())
+    }
+
+    /// The display names of the groups of `workspace`.
+    pub fn workspace_displays<'a>(
+        &'a self,
+        workspace: &'a Workspace,
+    ) -> impl Iterator<Item = &'a str> {
+        workspace
+            .groups
+            .iter()
+            .filter_map(|i| self.groups.get(*i))
+            .flat_map(|group| self.displays(&group.outputs))
+    }
+
+    pub fn find_output(&self, display: &str) -> Option<usize> {
+        self.outputs.iter().position(|o| o.display == display)
+    }
+
+    /// Find a workspace like [workspace::get_workspace] does.
+    pub fn find_workspace(&self, workspace: &WorkspaceIdent) -> Result<usize> {
+        if let Some(mark) = workspace.name.strip_prefix(marks::PREFIX) {
+            return self.find_workspace(&marks::workspace(mark)?);
+        }
+        let mut candidates = self
+            .workspaces
+            .iter()
+            .enumerate()
+            .filter(|(_, w)| w.name == workspace.name)
+            .filter(|(_, w)| {
+                workspace
+                    .display
+                    .as_ref()
+                    .is_none_or(|display| self.workspace_displays(w).any(|d| d == display))
+            });
+        match (candidates.next(), candidates.next()) {
+            (Some((i, _)), None) => Ok(i),
+            (Some(_), Some(_)) => bail!(Error::Ambiguous(format!(
+                "Found multiple workspaces with name {}. Specify display to narrow down selection",
+                workspace.name
+            ))),
+            (None, _) => bail!(Error::NotFound(format!(
+                "Workspace {} does not exist",
+                workspace.name
+            ))),
+        }
+    }
+
+    /// The number of toplevels on the workspace at `index`.
+    pub fn toplevel_count(&self, index: usize) -> usize {
+        self.toplevels
+            .iter()
+            .filter(|t| t.workspaces.contains(&index))
+            .count()
+    }
+}
+
+impl Toplevel {
+    pub fn has_state(&self, state: State) -> bool {
+        self.states.contains(&format!("{state:?}"))
+    }
+}
+
+/// The index of the workspace in [Snapshot::workspaces].
+pub fn workspace_index(app_data: &AppData, handle: &ExtWorkspaceHandleV1) -> Option<usize> {
+    app_data
+        .workspace_state
+        .workspaces()
+        .position(|w| w.handle == *handle)
+}
@@ -0,0 +1,64 @@
+use std::{fs, io::ErrorKind, path::PathBuf};
+
+use anyhow::{Context, Result};
+use log::debug;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{cosmic::AppData, ipc};
+
+/// The directory for state that is kept between commands.
+///
+/// It lives in [ipc::runtime_dir], so the state is gone after logging out,
+/// just like the toplevel identifiers it refers to.
+fn dir() -> Result<PathBuf> {
+    Ok(ipc::runtime_dir()?.join("cosmic-conductor"))
+}
+
+fn path(name: &str) -> Result<PathBuf> {
+    Ok(dir()?.join(format!("{name}.json")))
+}
+
+/// Read the state `name` or return the default if it was never saved.
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> Result<T> {
+    let path = path(name)?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(T::default()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("failed to read {}", path.display()));
+        }
+    };
+    serde_json::from_str(&content).with_context(|| format!("invalid state in {}", path.display()))
+}
+
+/// Replace the state `name` with `value`.
+///
+/// Like requests, nothing is written in a dry run.
+pub fn save<T: Serialize>(app_data: &AppData, name: &str, value: &T) -> Result<()> {
+    if app_data.dry_run {
+        debug!("dry run, not saving state {name}");
+        return Ok(());
+    }
+    fs::create_dir_all(dir()?)?;
+    let path = path(name)?;
+    // write to a temporary file first, so a concurrent load never sees half the state
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string(value)?)
+        .with_context(|| format!("failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, &path).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Forget the state `name`, unless this is a dry run.
+pub fn remove(app_data: &AppData, name: &str) -> Result<()> {
+    if app_data.dry_run {
+        debug!("dry run, not removing state {name}");
+        return Ok(());
+    }
+    let path = path(name)?;
+    match fs::remove_file(&path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => {
+            Err(err).with_context(|| format!("failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
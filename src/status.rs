@@ -0,0 +1,160 @@
+use std::io::Write;
+
+use anyhow::Result;
+use cosmic_protocols::toplevel_info::v1::client::zcosmic_toplevel_handle_v1::State as ToplevelState;
+use itertools::Itertools;
+use log::debug;
+use serde_json::json;
+use wayland_protocols::ext::workspace::v1::client::ext_workspace_handle_v1::State as WorkspaceState;
+
+use crate::{
+    args::Bar,
+    cosmic::{AppData, Session},
+    output,
+};
+
+/// Print a status line for `bar` whenever the state shown in it changes.
+///
+/// Runs until stdout is closed, e.g. because the bar exited.
+pub fn run(session: &mut Session, bar: Bar) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    let mut last = None;
+    loop {
+        let status = Status::new(&session.app_data);
+        if last.as_ref() != Some(&status) {
+            debug!("status changed: {status:?}");
+            writeln!(stdout, "{}", status.line(bar))?;
+            stdout.flush()?;
+            last = Some(status);
+        }
+        session
+            .event_queue
+            .blocking_dispatch(&mut session.app_data)?;
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Status {
+    /// display name and the active workspaces on it
+    displays: Vec<(String, Vec<String>)>,
+    urgent: Vec<String>,
+    /// title and app id of the focused toplevel
+    focused: Option<(String, String)>,
+}
+
+impl Status {
+    fn new(app_data: &AppData) -> Self {
+        let displays = app_data
+            .workspace_state
+            .workspace_groups()
+            .map(|group| {
+                let display = group
+                    .outputs
+                    .iter()
+                    .filter_map(|o| app_data.output_state.info(o))
+                    .map(|o| output::display_name(&o))
+                    .join(", ");
+                let active = group
+                    .workspaces
+                    .iter()
+                    .filter_map(|w| app_data.workspace_state.workspace_info(w))
+                    .filter(|w| w.state.contains(WorkspaceState::Active))
+                    .map(|w| w.name.clone())
+                    .collect();
+                (display, active)
+            })
+            .collect();
+
+        let urgent = app_data
+            .workspace_state
+            .workspaces()
+            .filter(|w| w.state.contains(WorkspaceState::Urgent))
+            .map(|w| w.name.clone())
+            .collect();
+
+        let focused = app_data
+            .toplevel_info_state
+            .toplevels()
+            .find(|t| t.state.contains(&ToplevelState::Activated))
+            .map(|t| (t.title.clone(), t.app_id.clone()));
+
+        Self {
+            displays,
+            urgent,
+            focused,
+        }
+    }
+
+    /// The active workspaces, prefixed with their display if there is more than one.
+    fn workspaces(&self) -> String {
+        if let [(_, active)] = self.displays.as_slice() {
+            return active.join(" ");
+        }
+        self.displays
+            .iter()
+            .map(|(display, active)| format!("{display}:{}", active.join(",")))
+            .join(" ")
+    }
+
+    fn text(&self) -> String {
+        let mut text = self.workspaces();
+        if !self.urgent.is_empty() {
+            text.push_str(&format!(" !{}", self.urgent.join(",")));
+        }
+        if let Some((title, _)) = &self.focused {
+            text.push_str(&format!(" — {title}"));
+        }
+        text
+    }
+
+    fn tooltip(&self) -> String {
+        let mut lines: Vec<String> = self
+            .displays
+            .iter()
+            .map(|(display, active)| format!("{display}: {}", active.join(", ")))
+            .collect();
+        if !self.urgent.is_empty() {
+            lines.push(format!("urgent: {}", self.urgent.join(", ")));
+        }
+        if let Some((title, app_id)) = &self.focused {
+            lines.push(format!("focused: {title} ({app_id})"));
+        }
+        lines.join("\n")
+    }
+
+    fn line(&self, bar: Bar) -> String {
+        match bar {
+            Bar::Waybar => {
+                let mut classes = Vec::new();
+                if !self.urgent.is_empty() {
+                    classes.push("urgent");
+                }
+                classes.push(if self.focused.is_some() {
+                    "focused"
+                } else {
+                    "empty"
+                });
+                // waybar interprets text and tooltip as pango markup
+                json!({
+                    "text": escape_markup(&self.text()),
+                    "tooltip": escape_markup(&self.tooltip()),
+                    "class": classes,
+                })
+                .to_string()
+            }
+            Bar::I3blocks => json!({
+                "full_text": self.text(),
+                "short_text": self.workspaces(),
+                "urgent": !self.urgent.is_empty(),
+            })
+            .to_string(),
+        }
+    }
+}
+
+fn escape_markup(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -1,21 +1,31 @@
 use std::io::Write;
 
 use anyhow::{Context, Result, bail};
-use cosmic_client_toolkit::toplevel_info::ToplevelInfo;
-use cosmic_protocols::toplevel_info::v1::client::zcosmic_toplevel_handle_v1::State;
+use cosmic_client_toolkit::{
+    toplevel_info::ToplevelInfo,
+    workspace::{Workspace, WorkspaceGroup},
+};
+use cosmic_protocols::toplevel_info::v1::client::zcosmic_toplevel_handle_v1::{
+    State, ZcosmicToplevelHandleV1,
+};
 use itertools::Itertools;
 use log::warn;
+use wayland_client::protocol::{wl_output::WlOutput, wl_seat::WlSeat};
+use wayland_protocols::ext::workspace::v1::client::ext_workspace_handle_v1::State as WorkspaceState;
 
 use crate::{
     args::WorkspaceIdent,
     cosmic::AppData,
-    output,
-    print::{DebugToDisplay, Print, PrintList},
-    workspace::get_workspace,
+    error::Error,
+    marks,
+    print::{Print, PrintList},
+    request::{Request, ToplevelRequest},
+    snapshot::{self, Snapshot},
+    workspace::{self, get_workspace},
 };
 
 pub fn list<W: Write>(
-    app_data: &AppData,
+    snapshot: &Snapshot,
     printer: &mut impl Print<W>,
     workspace: Option<String>,
     display: Option<String>,
@@ -27,73 +37,105 @@ pub fn list<W: Write>(
                 name: workspace,
                 display,
             };
-            let (_, _, workspace) = get_workspace(app_data, &workspace_id)?;
-            crate::workspace::workspace_toplevels(workspace, app_data).collect()
+            let workspace = snapshot.find_workspace(&workspace_id)?;
+            snapshot
+                .toplevels
+                .iter()
+                .filter(|t| t.workspaces.contains(&workspace))
+                .collect()
         }
         (None, Some(display)) => {
-            let Some(display) = output::find(app_data, &display) else {
-                bail!("unknown display: {display}");
+            let Some(display) = snapshot.find_output(&display) else {
+                bail!(Error::NotFound(format!("unknown display: {display}")));
             };
-            app_data
-                .toplevel_info_state
-                .toplevels()
-                .filter(|t| t.output.contains(&display.0))
+            snapshot
+                .toplevels
+                .iter()
+                .filter(|t| t.outputs.contains(&display))
                 .collect()
         }
-        _ => app_data.toplevel_info_state.toplevels().collect(),
+        _ => snapshot.toplevels.iter().collect(),
     };
 
     let mut printer = printer.sub_list("Toplevels")?;
     for toplevel in toplevels {
-        let mut printer = printer.sub_struct()?;
-
-        printer.field("Title", &toplevel.title)?;
-        printer.field("AppId", &toplevel.app_id)?;
-        printer.field("Unique Identifier", &toplevel.identifier)?;
-        let states = toplevel.state.iter().map(DebugToDisplay);
-        printer.inline_list("State", states)?;
-        let workspace = toplevel
-            .workspace
-            .iter()
-            .filter_map(|w| app_data.workspace_state.workspace_info(w))
-            .map(|w| w.name.as_str())
-            .exactly_one()
-            .ok();
-        printer.optional("workspace", workspace)?;
-        let output = toplevel.output.iter().exactly_one().ok();
-        let output_name = output
-            .and_then(|handle| app_data.output_state.info(handle))
-            .map(|o| output::display_name(&o));
-        printer.optional("output", output_name)?;
-        if show_geometry {
-            let Some(output) = output else {
-                warn!("no output found for toplevel: {}", toplevel.title);
-                continue;
-            };
-            let Some(geometry) = toplevel.geometry.get(output) else {
-                warn!("no geometry found for toplevel: {}", toplevel.title);
-                continue;
-            };
-            let mut printer = printer.sub_struct("Geometry")?;
-            printer.field("x", geometry.x)?;
-            printer.field("y", geometry.y)?;
-            printer.field("width", geometry.width)?;
-            printer.field("height", geometry.height)?;
-        }
+        print_toplevel(
+            snapshot,
+            &mut printer.sub_struct()?,
+            toplevel,
+            show_geometry,
+        )?;
     }
 
     Ok(())
 }
 
+pub fn print_toplevel<W: Write>(
+    snapshot: &Snapshot,
+    printer: &mut impl Print<W>,
+    toplevel: &snapshot::Toplevel,
+    show_geometry: bool,
+) -> Result<()> {
+    printer.field("Title", &toplevel.title)?;
+    printer.field("AppId", &toplevel.app_id)?;
+    printer.field("Unique Identifier", &toplevel.identifier)?;
+    printer.inline_list("State", toplevel.states.iter())?;
+    let workspace = toplevel
+        .workspaces
+        .iter()
+        .filter_map(|i| snapshot.workspaces.get(*i))
+        .map(|w| w.name.as_str())
+        .exactly_one()
+        .ok();
+    printer.optional("workspace", workspace)?;
+    let output = toplevel.outputs.iter().exactly_one().ok();
+    let output_name = output
+        .and_then(|i| snapshot.outputs.get(*i))
+        .map(|o| o.display.as_str());
+    printer.optional("output", output_name)?;
+    if show_geometry {
+        let Some(output) = output else {
+            warn!("no output found for toplevel: {}", toplevel.title);
+            return Ok(());
+        };
+        let Some((_, geometry)) = toplevel.geometry.iter().find(|(o, _)| o == output) else {
+            warn!("no geometry found for toplevel: {}", toplevel.title);
+            return Ok(());
+        };
+        let mut printer = printer.sub_struct("Geometry")?;
+        printer.field("x", geometry.x)?;
+        printer.field("y", geometry.y)?;
+        printer.field("width", geometry.width)?;
+        printer.field("height", geometry.height)?;
+    }
+
+    Ok(())
+}
+
+/// Find the toplevel by the start of its identifier or by a mark like "@editor".
 pub fn find_from_id<'a>(app_data: &'a AppData, id: &str) -> Result<&'a ToplevelInfo> {
+    if let Some(mark) = id.strip_prefix(marks::PREFIX) {
+        let identifier = marks::toplevel(mark)?;
+        return app_data
+            .toplevel_info_state
+            .toplevels()
+            .find(|t| t.identifier == identifier)
+            .ok_or_else(|| {
+                Error::NotFound(format!("the toplevel marked {id} no longer exists")).into()
+            });
+    }
     let mut matches = app_data
         .toplevel_info_state
         .toplevels()
         .filter(|t| t.identifier.starts_with(id));
     match (matches.next(), matches.next()) {
         (Some(toplevel), None) => Ok(toplevel),
-        (Some(_), Some(_)) => bail!("id \"{id}\" is not unique for toplevels"),
-        (None, _) => bail!("Could not find toplevel with id: {id}"),
+        (Some(_), Some(_)) => bail!(Error::Ambiguous(format!(
+            "id \"{id}\" is not unique for toplevels"
+        ))),
+        (None, _) => bail!(Error::NotFound(format!(
+            "Could not find toplevel with id: {id}"
+        ))),
     }
 }
 
@@ -113,112 +155,94 @@ impl SetStateAction {
             (false, false) => Ok(Self::Set),
         }
     }
+
+    /// Returns whether the state should be set, given whether it is `currently_set`.
+    pub fn resolve(self, currently_set: bool) -> bool {
+        match self {
+            SetStateAction::Set => true,
+            SetStateAction::Unset => false,
+            SetStateAction::Toggle => !currently_set,
+        }
+    }
 }
 
-pub fn maximize(app_data: &AppData, id: &str, action: SetStateAction) -> Result<()> {
-    let toplevel = find_from_id(app_data, id)?;
+fn cosmic_handle(toplevel: &ToplevelInfo) -> Result<&ZcosmicToplevelHandleV1> {
     let Some(handle) = toplevel.cosmic_toplevel.as_ref() else {
-        bail!(
+        bail!(Error::Internal(format!(
             "INTERNAL: No cosmic handle for toplevel {}",
             toplevel.identifier
-        );
+        )));
     };
-    let manager = &app_data.toplevel_manager_state.manager;
-
-    match action {
-        SetStateAction::Set => manager.set_maximized(handle),
-        SetStateAction::Unset => manager.unset_maximized(handle),
-        SetStateAction::Toggle => {
-            if toplevel.state.contains(&State::Maximized) {
-                manager.unset_maximized(handle);
-            } else {
-                manager.set_maximized(handle);
-            }
-        }
-    }
+    Ok(handle)
+}
+
+pub fn maximize(app_data: &AppData, toplevel: &ToplevelInfo, action: SetStateAction) -> Result<()> {
+    let handle = cosmic_handle(toplevel)?;
+    let set = action.resolve(toplevel.state.contains(&State::Maximized));
+    app_data.send(Request::toplevel(
+        toplevel,
+        handle,
+        ToplevelRequest::Maximize(set),
+    ))?;
 
     Ok(())
 }
 
-pub fn fullscreen(app_data: &AppData, id: &str, action: SetStateAction) -> Result<()> {
-    let toplevel = find_from_id(app_data, id)?;
-    let Some(handle) = toplevel.cosmic_toplevel.as_ref() else {
-        bail!(
-            "INTERNAL: No cosmic handle for toplevel {}",
-            toplevel.identifier
-        );
-    };
-    let manager = &app_data.toplevel_manager_state.manager;
-
-    match action {
-        SetStateAction::Set => manager.set_fullscreen(handle, None),
-        SetStateAction::Unset => manager.unset_fullscreen(handle),
-        SetStateAction::Toggle => {
-            if toplevel.state.contains(&State::Fullscreen) {
-                manager.unset_fullscreen(handle);
-            } else {
-                manager.set_fullscreen(handle, None);
-            }
-        }
-    }
+pub fn fullscreen(
+    app_data: &AppData,
+    toplevel: &ToplevelInfo,
+    action: SetStateAction,
+) -> Result<()> {
+    let handle = cosmic_handle(toplevel)?;
+    let set = action.resolve(toplevel.state.contains(&State::Fullscreen));
+    app_data.send(Request::toplevel(
+        toplevel,
+        handle,
+        ToplevelRequest::Fullscreen(set),
+    ))?;
 
     Ok(())
 }
 
-pub fn minimize(app_data: &AppData, id: &str, action: SetStateAction) -> Result<()> {
-    let toplevel = find_from_id(app_data, id)?;
-    let Some(handle) = toplevel.cosmic_toplevel.as_ref() else {
-        bail!(
-            "INTERNAL: No cosmic handle for toplevel {}",
-            toplevel.identifier
-        );
-    };
-    let manager = &app_data.toplevel_manager_state.manager;
-
-    match action {
-        SetStateAction::Set => manager.set_minimized(handle),
-        SetStateAction::Unset => manager.unset_minimized(handle),
-        SetStateAction::Toggle => {
-            if toplevel.state.contains(&State::Minimized) {
-                manager.unset_minimized(handle);
-            } else {
-                manager.set_minimized(handle);
-            }
-        }
-    }
+pub fn minimize(app_data: &AppData, toplevel: &ToplevelInfo, action: SetStateAction) -> Result<()> {
+    let handle = cosmic_handle(toplevel)?;
+    let set = action.resolve(toplevel.state.contains(&State::Minimized));
+    app_data.send(Request::toplevel(
+        toplevel,
+        handle,
+        ToplevelRequest::Minimize(set),
+    ))?;
 
     Ok(())
 }
 
-pub fn sticky(app_data: &AppData, id: &str, action: SetStateAction) -> Result<()> {
-    let toplevel = find_from_id(app_data, id)?;
-    let Some(handle) = toplevel.cosmic_toplevel.as_ref() else {
-        bail!(
-            "INTERNAL: No cosmic handle for toplevel {}",
-            toplevel.identifier
-        );
-    };
-    let manager = &app_data.toplevel_manager_state.manager;
-
-    match action {
-        SetStateAction::Set => manager.set_sticky(handle),
-        SetStateAction::Unset => manager.unset_sticky(handle),
-        SetStateAction::Toggle => {
-            if toplevel.state.contains(&State::Sticky) {
-                manager.unset_sticky(handle);
-            } else {
-                manager.set_sticky(handle);
-            }
-        }
-    }
+pub fn sticky(app_data: &AppData, toplevel: &ToplevelInfo, action: SetStateAction) -> Result<()> {
+    let handle = cosmic_handle(toplevel)?;
+    let set = action.resolve(toplevel.state.contains(&State::Sticky));
+    app_data.send(Request::toplevel(
+        toplevel,
+        handle,
+        ToplevelRequest::Sticky(set),
+    ))?;
 
     Ok(())
 }
 
-pub fn move_to(app_data: &AppData, id: &str, workspace: WorkspaceIdent) -> Result<()> {
-    let toplevel = find_from_id(app_data, id)?;
-    let (group, _, workspace) = get_workspace(app_data, &workspace)?;
+pub fn move_to(
+    app_data: &AppData,
+    toplevel: &ToplevelInfo,
+    workspace: &WorkspaceIdent,
+) -> Result<()> {
+    let (group, _, workspace) = get_workspace(app_data, workspace)?;
+    move_to_workspace(app_data, toplevel, group, workspace)
+}
 
+fn move_to_workspace(
+    app_data: &AppData,
+    toplevel: &ToplevelInfo,
+    group: &WorkspaceGroup,
+    workspace: &Workspace,
+) -> Result<()> {
     let output = group
         .outputs
         .iter()
@@ -226,42 +250,167 @@ pub fn move_to(app_data: &AppData, id: &str, workspace: WorkspaceIdent) -> Resul
         .ok()
         .context("Failed to get output for workspace group")?;
 
-    let Some(handle) = toplevel.cosmic_toplevel.as_ref() else {
-        bail!(
-            "INTERNAL: No cosmic handle for toplevel {}",
+    let handle = cosmic_handle(toplevel)?;
+
+    app_data.send(Request::toplevel(
+        toplevel,
+        handle,
+        ToplevelRequest::MoveToWorkspace {
+            workspace: workspace.handle.clone(),
+            workspace_name: workspace.name.clone(),
+            output: output.clone(),
+        },
+    ))?;
+
+    Ok(())
+}
+
+/// The workspace of the toplevel and its group.
+fn workspace_of<'a>(
+    app_data: &'a AppData,
+    toplevel: &ToplevelInfo,
+) -> Result<(&'a WorkspaceGroup, &'a Workspace)> {
+    let workspaces: Vec<_> = toplevel.workspace.iter().collect();
+    let handle = match workspaces.as_slice() {
+        [handle] => *handle,
+        [] => bail!(Error::NotFound(format!(
+            "toplevel {} is not on any workspace",
             toplevel.identifier
-        );
+        ))),
+        _ => bail!(Error::Ambiguous(format!(
+            "toplevel {} is on more than one workspace",
+            toplevel.identifier
+        ))),
     };
+    let Some(workspace) = app_data.workspace_state.workspace_info(handle) else {
+        bail!(Error::Internal(format!(
+            "INTERNAL: No info for the workspace of toplevel {}",
+            toplevel.identifier
+        )));
+    };
+    let Some(group) = workspace::get_groups_for_workspace(workspace, app_data).next() else {
+        bail!(Error::Internal(format!(
+            "INTERNAL: No group for workspace {}",
+            workspace.name
+        )));
+    };
+    Ok((group, workspace))
+}
 
+/// Move each toplevel to the workspace of the other one.
+pub fn swap(app_data: &AppData, first: &ToplevelInfo, second: &ToplevelInfo) -> Result<()> {
+    let (first_group, first_workspace) = workspace_of(app_data, first)?;
+    let (second_group, second_workspace) = workspace_of(app_data, second)?;
+    if first_workspace.handle == second_workspace.handle {
+        bail!(
+            "no other workspace to swap with, both toplevels are on workspace {}",
+            first_workspace.name
+        );
+    }
+
+    move_to_workspace(app_data, first, second_group, second_workspace)?;
+    move_to_workspace(app_data, second, first_group, first_workspace)
+}
+
+fn seat(app_data: &AppData) -> Result<WlSeat> {
     app_data
-        .toplevel_manager_state
-        .manager
-        .move_to_ext_workspace(handle, &workspace.handle, output);
+        .seat_state
+        .seats()
+        .exactly_one()
+        .ok()
+        .context("Could not get wayland seat")
+}
+
+pub fn activate(app_data: &AppData, toplevel: &ToplevelInfo) -> Result<()> {
+    let seat = seat(app_data)?;
+    let handle = cosmic_handle(toplevel)?;
+
+    app_data.send(Request::toplevel(
+        toplevel,
+        handle,
+        ToplevelRequest::Activate(seat),
+    ))?;
 
     Ok(())
 }
 
-pub fn activate(app_data: &AppData, id: &str) -> Result<()> {
-    let toplevel = find_from_id(app_data, id)?;
+pub fn close(app_data: &AppData, toplevel: &ToplevelInfo) -> Result<()> {
+    let handle = cosmic_handle(toplevel)?;
 
-    let seat = app_data
-        .seat_state
-        .seats()
+    app_data.send(Request::toplevel(toplevel, handle, ToplevelRequest::Close))?;
+
+    Ok(())
+}
+
+/// The display the user is working on, which is the display of the focused
+/// toplevel or the only display.
+fn focused_output(app_data: &AppData) -> Result<WlOutput> {
+    let focused = app_data
+        .toplevel_info_state
+        .toplevels()
+        .find(|t| t.state.contains(&State::Activated));
+    if let Some(output) = focused.and_then(|t| t.output.iter().exactly_one().ok()) {
+        return Ok(output.clone());
+    }
+    app_data
+        .output_state
+        .outputs()
         .exactly_one()
         .ok()
-        .context("Could not get wayland seat")?;
+        .context("Could not determine the focused display")
+}
 
-    let Some(handle) = toplevel.cosmic_toplevel.as_ref() else {
-        bail!(
-            "INTERNAL: No cosmic handle for toplevel {}",
-            toplevel.identifier
-        );
+/// Move the toplevel to the active workspace of the focused display,
+/// unminimize it and activate it.
+pub fn summon(app_data: &AppData, toplevel: &ToplevelInfo) -> Result<()> {
+    let handle = cosmic_handle(toplevel)?;
+
+    let output = focused_output(app_data)?;
+    let Some(group) = app_data
+        .workspace_state
+        .workspace_groups()
+        .find(|group| group.outputs.contains(&output))
+    else {
+        bail!(Error::NotFound(
+            "No workspace group on the focused display".to_string()
+        ));
+    };
+    let Some(workspace) = group
+        .workspaces
+        .iter()
+        .filter_map(|w| app_data.workspace_state.workspace_info(w))
+        .find(|w| w.state.contains(WorkspaceState::Active))
+    else {
+        bail!(Error::NotFound(
+            "No active workspace on the focused display".to_string()
+        ));
     };
 
-    app_data
-        .toplevel_manager_state
-        .manager
-        .activate(handle, &seat);
+    let seat = seat(app_data)?;
+
+    if !toplevel.workspace.contains(&workspace.handle) {
+        app_data.send(Request::toplevel(
+            toplevel,
+            handle,
+            ToplevelRequest::MoveToWorkspace {
+                workspace: workspace.handle.clone(),
+                workspace_name: workspace.name.clone(),
+                output,
+            },
+        ))?;
+    }
+    if toplevel.state.contains(&State::Minimized) {
+        app_data.send(Request::toplevel(
+            toplevel,
+            handle,
+            ToplevelRequest::Minimize(false),
+        ))?;
+    }
+    app_data.send(Request::toplevel(
+        toplevel,
+        handle,
+        ToplevelRequest::Activate(seat),
+    ))?;
 
     Ok(())
 }
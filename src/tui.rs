@@ -0,0 +1,441 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use cosmic_client_toolkit::{toplevel_info::ToplevelInfo, workspace::Workspace};
+use cosmic_protocols::workspace::v2::client::zcosmic_workspace_handle_v2::State as CosmicWorkspaceState;
+use itertools::Itertools;
+use log::debug;
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph},
+};
+use wayland_protocols::ext::workspace::v1::client::{
+    ext_workspace_group_handle_v1::ExtWorkspaceGroupHandleV1,
+    ext_workspace_handle_v1::{ExtWorkspaceHandleV1, State as WorkspaceState},
+};
+
+use crate::{
+    args::WorkspaceIdent,
+    cosmic::{AppData, Session},
+    output,
+    request::Request,
+    toplevel::{self, SetStateAction},
+    workspace,
+};
+
+/// How long to wait for a key press before dispatching compositor events.
+const TICK: Duration = Duration::from_millis(100);
+
+const HELP: &str = "↑/↓ select  enter activate  m minimize  p pin  v move  c close  q quit";
+
+/// Run the terminal dashboard until the user quits.
+pub fn run(session: &mut Session) -> Result<()> {
+    let mut terminal = ratatui::try_init()?;
+    let result = event_loop(&mut terminal, session);
+    ratatui::try_restore()?;
+    debug!("tui exited");
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, session: &mut Session) -> Result<()> {
+    let mut tui = Tui::default();
+    loop {
+        session.dispatch_pending()?;
+
+        let app_data = &session.app_data;
+        let rows = rows(app_data);
+        tui.sync_selection(&rows);
+        terminal.draw(|frame| tui.draw(frame, app_data, &rows))?;
+
+        if !event::poll(TICK)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if !tui.handle_key(key, app_data, &rows) {
+            return Ok(());
+        }
+    }
+}
+
+/// Something that can be selected in the workspace tree.
+#[derive(Debug, Clone, PartialEq)]
+enum Item {
+    Group(ExtWorkspaceGroupHandleV1),
+    Workspace(ExtWorkspaceHandleV1),
+    Toplevel(String),
+    NoWorkspace,
+}
+
+struct Row {
+    item: Item,
+    line: Line<'static>,
+}
+
+enum Mode {
+    Normal,
+    /// Reading the target of a move for `item`.
+    Move {
+        item: Item,
+        input: String,
+    },
+}
+
+struct Tui {
+    list_state: ListState,
+    selected: Option<Item>,
+    mode: Mode,
+    status: String,
+}
+
+impl Default for Tui {
+    fn default() -> Self {
+        Self {
+            list_state: ListState::default(),
+            selected: None,
+            mode: Mode::Normal,
+            status: String::new(),
+        }
+    }
+}
+
+impl Tui {
+    /// Keep the selected item selected even if rows were added or removed.
+    fn sync_selection(&mut self, rows: &[Row]) {
+        let index = self
+            .selected
+            .as_ref()
+            .and_then(|item| rows.iter().position(|row| &row.item == item))
+            .or_else(|| self.list_state.selected())
+            .unwrap_or(0)
+            .min(rows.len().saturating_sub(1));
+        self.select(rows, index);
+    }
+
+    fn select(&mut self, rows: &[Row], index: usize) {
+        match rows.get(index) {
+            Some(row) => {
+                self.list_state.select(Some(index));
+                self.selected = Some(row.item.clone());
+            }
+            None => {
+                self.list_state.select(None);
+                self.selected = None;
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame, app_data: &AppData, rows: &[Row]) {
+        let outputs = output_lines(app_data);
+        let [outputs_area, tree_area, status_area, help_area] = Layout::vertical([
+            Constraint::Length(outputs.len() as u16 + 2),
+            Constraint::Min(0),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        frame.render_widget(
+            Paragraph::new(outputs).block(Block::bordered().title("Outputs")),
+            outputs_area,
+        );
+
+        let list = List::new(rows.iter().map(|row| ListItem::new(row.line.clone())))
+            .block(Block::bordered().title("Workspaces"))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, tree_area, &mut self.list_state);
+
+        let status = match &self.mode {
+            Mode::Normal => Line::from(self.status.clone()),
+            Mode::Move {
+                item: Item::Workspace(_),
+                input,
+            } => Line::from(format!("move to position (position[@display]): {input}")),
+            Mode::Move { input, .. } => {
+                Line::from(format!("move to workspace (name[@display]): {input}"))
+            }
+        };
+        frame.render_widget(Paragraph::new(status), status_area);
+        frame.render_widget(
+            Paragraph::new(HELP).style(Style::new().add_modifier(Modifier::DIM)),
+            help_area,
+        );
+    }
+
+    /// Returns `false` once the user wants to quit.
+    fn handle_key(&mut self, key: KeyEvent, app_data: &AppData, rows: &[Row]) -> bool {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return false;
+        }
+
+        if let Mode::Move { input, .. } = &mut self.mode {
+            match key.code {
+                KeyCode::Esc => self.mode = Mode::Normal,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Enter => {
+                    if let Mode::Move { item, input } =
+                        std::mem::replace(&mut self.mode, Mode::Normal)
+                    {
+                        self.apply(app_data, |app_data| move_item(app_data, &item, &input));
+                    }
+                }
+                _ => {}
+            }
+            return true;
+        }
+
+        let current = self.list_state.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.select(rows, current.saturating_sub(1)),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.select(rows, (current + 1).min(rows.len().saturating_sub(1)))
+            }
+            KeyCode::Home | KeyCode::Char('g') => self.select(rows, 0),
+            KeyCode::End | KeyCode::Char('G') => self.select(rows, rows.len().saturating_sub(1)),
+            _ => {}
+        }
+
+        let Some(item) = self.selected.clone() else {
+            return true;
+        };
+        match key.code {
+            KeyCode::Enter | KeyCode::Char('a') => {
+                self.apply(app_data, |app_data| activate(app_data, &item))
+            }
+            KeyCode::Char('m') => self.apply(app_data, |app_data| minimize(app_data, &item)),
+            KeyCode::Char('p') => self.apply(app_data, |app_data| pin(app_data, &item)),
+            KeyCode::Char('c') => self.apply(app_data, |app_data| close(app_data, &item)),
+            KeyCode::Char('v') => match item {
+                Item::Workspace(_) | Item::Toplevel(_) => {
+                    self.mode = Mode::Move {
+                        item,
+                        input: String::new(),
+                    }
+                }
+                _ => self.status = "only workspaces and toplevels can be moved".into(),
+            },
+            _ => {}
+        }
+        true
+    }
+
+    /// Run `action` and show the requests it sent or the error in the status line.
+    fn apply(&mut self, app_data: &AppData, action: impl FnOnce(&AppData) -> Result<()>) {
+        app_data.requests.borrow_mut().clear();
+        self.status = match action(app_data) {
+            Ok(()) => app_data
+                .requests
+                .borrow()
+                .iter()
+                .filter(|r| !matches!(r, Request::WorkspaceCommit))
+                .join(", "),
+            Err(err) => format!("Error: {err:#}"),
+        };
+    }
+}
+
+fn activate(app_data: &AppData, item: &Item) -> Result<()> {
+    match item {
+        Item::Toplevel(id) => toplevel::activate(app_data, toplevel::find_from_id(app_data, id)?),
+        Item::Workspace(handle) => {
+            workspace::activate(app_data, workspace_ident(app_data, handle)?)
+        }
+        _ => bail!("only workspaces and toplevels can be activated"),
+    }
+}
+
+fn minimize(app_data: &AppData, item: &Item) -> Result<()> {
+    let Item::Toplevel(id) = item else {
+        bail!("only toplevels can be minimized");
+    };
+    toplevel::minimize(
+        app_data,
+        toplevel::find_from_id(app_data, id)?,
+        SetStateAction::Toggle,
+    )
+}
+
+fn close(app_data: &AppData, item: &Item) -> Result<()> {
+    let Item::Toplevel(id) = item else {
+        bail!("only toplevels can be closed");
+    };
+    toplevel::close(app_data, toplevel::find_from_id(app_data, id)?)
+}
+
+fn pin(app_data: &AppData, item: &Item) -> Result<()> {
+    let Item::Workspace(handle) = item else {
+        bail!("only workspaces can be pinned");
+    };
+    let pinned = app_data
+        .workspace_state
+        .workspace_info(handle)
+        .is_some_and(|w| w.cosmic_state.contains(CosmicWorkspaceState::Pinned));
+    workspace::pin(app_data, workspace_ident(app_data, handle)?, !pinned)
+}
+
+/// Move a toplevel to the workspace or a workspace to the position in `input`.
+///
+/// Both accept an optional "@display" suffix.
+fn move_item(app_data: &AppData, item: &Item, input: &str) -> Result<()> {
+    let (target, display) = match input.trim().split_once('@') {
+        Some((target, display)) => (target, Some(display)),
+        None => (input.trim(), None),
+    };
+    match item {
+        Item::Toplevel(id) => toplevel::move_to(
+            app_data,
+            toplevel::find_from_id(app_data, id)?,
+            &WorkspaceIdent {
+                name: target.to_string(),
+                display: display.map(String::from),
+            },
+        ),
+        Item::Workspace(handle) => {
+            let position = target
+                .parse()
+                .with_context(|| format!("invalid position: {target}"))?;
+            workspace::move_to(
+                app_data,
+                workspace_ident(app_data, handle)?,
+                position,
+                display,
+            )
+        }
+        _ => bail!("only workspaces and toplevels can be moved"),
+    }
+}
+
+fn workspace_ident(app_data: &AppData, handle: &ExtWorkspaceHandleV1) -> Result<WorkspaceIdent> {
+    let workspace = app_data
+        .workspace_state
+        .workspace_info(handle)
+        .context("workspace no longer exists")?;
+    let display = workspace::get_groups_for_workspace(workspace, app_data)
+        .flat_map(|group| group.outputs.iter())
+        .filter_map(|o| app_data.output_state.info(o))
+        .map(|o| output::display_name(&o))
+        .next();
+    Ok(WorkspaceIdent {
+        name: workspace.name.clone(),
+        display,
+    })
+}
+
+/// The workspace tree: every group with its workspaces and their toplevels,
+/// followed by the toplevels that are not on any workspace.
+fn rows(app_data: &AppData) -> Vec<Row> {
+    let mut rows = Vec::new();
+    for group in app_data.workspace_state.workspace_groups() {
+        let displays = group
+            .outputs
+            .iter()
+            .filter_map(|o| app_data.output_state.info(o))
+            .map(|o| output::display_name(&o))
+            .join(", ");
+        rows.push(Row {
+            item: Item::Group(group.handle.clone()),
+            line: Line::styled(displays, Style::new().add_modifier(Modifier::BOLD)),
+        });
+
+        for workspace in group
+            .workspaces
+            .iter()
+            .filter_map(|w| app_data.workspace_state.workspace_info(w))
+        {
+            rows.push(Row {
+                item: Item::Workspace(workspace.handle.clone()),
+                line: workspace_line(workspace),
+            });
+            for toplevel in workspace::workspace_toplevels(workspace, app_data) {
+                rows.push(Row {
+                    item: Item::Toplevel(toplevel.identifier.clone()),
+                    line: toplevel_line(toplevel),
+                });
+            }
+        }
+    }
+
+    let mut without_workspace = app_data
+        .toplevel_info_state
+        .toplevels()
+        .filter(|t| t.workspace.is_empty())
+        .peekable();
+    if without_workspace.peek().is_some() {
+        rows.push(Row {
+            item: Item::NoWorkspace,
+            line: Line::styled("no workspace", Style::new().add_modifier(Modifier::BOLD)),
+        });
+        rows.extend(without_workspace.map(|toplevel| Row {
+            item: Item::Toplevel(toplevel.identifier.clone()),
+            line: toplevel_line(toplevel),
+        }));
+    }
+
+    rows
+}
+
+fn workspace_line(workspace: &Workspace) -> Line<'static> {
+    let mut spans = vec![Span::raw(format!("  {}", workspace.name))];
+    if workspace.state.contains(WorkspaceState::Active) {
+        spans.push(Span::styled(" active", Style::new().fg(Color::Green)));
+    }
+    if workspace.state.contains(WorkspaceState::Urgent) {
+        spans.push(Span::styled(" urgent", Style::new().fg(Color::Red)));
+    }
+    if workspace
+        .cosmic_state
+        .contains(CosmicWorkspaceState::Pinned)
+    {
+        spans.push(Span::styled(" pinned", Style::new().fg(Color::Yellow)));
+    }
+    Line::from(spans)
+}
+
+fn toplevel_line(toplevel: &ToplevelInfo) -> Line<'static> {
+    let mut spans = vec![Span::raw(format!(
+        "    {} — {}",
+        toplevel.title, toplevel.app_id
+    ))];
+    if !toplevel.state.is_empty() {
+        let states = toplevel
+            .state
+            .iter()
+            .map(|s| format!("{s:?}").to_lowercase())
+            .join(", ");
+        spans.push(Span::styled(
+            format!(" [{states}]"),
+            Style::new().add_modifier(Modifier::DIM),
+        ));
+    }
+    Line::from(spans)
+}
+
+fn output_lines(app_data: &AppData) -> Vec<Line<'static>> {
+    app_data
+        .output_state
+        .outputs()
+        .filter_map(|o| app_data.output_state.info(&o))
+        .map(|info| {
+            let mut line = output::display_name(&info);
+            if let (Some((width, height)), Some((x, y))) =
+                (info.logical_size, info.logical_position)
+            {
+                line.push_str(&format!("  {width}x{height}+{x}+{y}"));
+            }
+            line.push_str(&format!("  scale {}", info.scale_factor));
+            Line::from(line)
+        })
+        .collect()
+}
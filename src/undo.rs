@@ -0,0 +1,367 @@
+use std::{io::Write, time::Duration};
+
+use anyhow::{Result, bail};
+use cosmic_client_toolkit::workspace::Workspace;
+use cosmic_protocols::{
+    toplevel_info::v1::client::zcosmic_toplevel_handle_v1::State,
+    workspace::v2::client::zcosmic_workspace_handle_v2::State as CosmicWorkspaceState,
+};
+use itertools::Itertools;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use wayland_protocols::ext::workspace::v1::client::ext_workspace_handle_v1::State as WorkspaceState;
+
+use crate::{
+    args::WorkspaceIdent,
+    cosmic::{AppData, Session},
+    print::{Print, PrintList},
+    request::{Request, ToplevelRequest, WorkspaceRequest},
+    state,
+    toplevel::{self, SetStateAction},
+    verify,
+    workspace::{self, get_groups_for_workspace, get_workspace},
+};
+
+const STATE_NAME: &str = "journal";
+
+/// How many commands can be undone.
+const MAX_ENTRIES: usize = 32;
+
+/// A toplevel state that can be set and unset.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToplevelFlag {
+    Maximized,
+    Minimized,
+    Fullscreen,
+    Sticky,
+}
+
+/// Restores what a single request changed.
+///
+/// Toplevels are referred to by identifier and workspaces by name and display,
+/// because the wayland handles only live as long as the connection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Inverse {
+    ToplevelFlag {
+        identifier: String,
+        flag: ToplevelFlag,
+        set: bool,
+    },
+    ToplevelWorkspace {
+        identifier: String,
+        workspace: String,
+        display: Option<String>,
+    },
+    ToplevelActivate {
+        identifier: String,
+    },
+    WorkspacePin {
+        name: String,
+        display: Option<String>,
+        pin: bool,
+    },
+    WorkspaceActivate {
+        name: String,
+        display: Option<String>,
+    },
+    /// Move a workspace back to the display it came from. Its position is
+    /// restored by the [Inverse::WorkspaceOrder] recorded with it.
+    WorkspaceDisplay {
+        name: String,
+        from: Option<String>,
+        to: String,
+    },
+    WorkspaceOrder {
+        display: String,
+        names: Vec<String>,
+    },
+}
+
+/// Everything a single command changed.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    /// the requests of the command
+    description: String,
+    inverses: Vec<Inverse>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Journal {
+    entries: Vec<Entry>,
+}
+
+/// What restores the state from before `request`.
+///
+/// Has to be called before the request is sent.
+pub fn inverses(request: &Request, app_data: &AppData) -> Vec<Inverse> {
+    match request {
+        Request::Toplevel {
+            identifier, action, ..
+        } => {
+            let Some(toplevel) = app_data
+                .toplevel_info_state
+                .toplevels()
+                .find(|t| &t.identifier == identifier)
+            else {
+                return Vec::new();
+            };
+            let flag = |flag: ToplevelFlag, state: State| Inverse::ToplevelFlag {
+                identifier: identifier.clone(),
+                flag,
+                set: toplevel.state.contains(&state),
+            };
+            match action {
+                ToplevelRequest::Maximize(_) => {
+                    vec![flag(ToplevelFlag::Maximized, State::Maximized)]
+                }
+                ToplevelRequest::Minimize(_) => {
+                    vec![flag(ToplevelFlag::Minimized, State::Minimized)]
+                }
+                ToplevelRequest::Fullscreen(_) => {
+                    vec![flag(ToplevelFlag::Fullscreen, State::Fullscreen)]
+                }
+                ToplevelRequest::Sticky(_) => vec![flag(ToplevelFlag::Sticky, State::Sticky)],
+                ToplevelRequest::Activate(_) => app_data
+                    .toplevel_info_state
+                    .toplevels()
+                    .filter(|t| t.state.contains(&State::Activated))
+                    .filter(|t| &t.identifier != identifier)
+                    .map(|t| Inverse::ToplevelActivate {
+                        identifier: t.identifier.clone(),
+                    })
+                    .collect(),
+                ToplevelRequest::Close => {
+                    debug!("closing toplevel {identifier} can not be undone");
+                    Vec::new()
+                }
+                ToplevelRequest::MoveToWorkspace { .. } => toplevel
+                    .workspace
+                    .iter()
+                    .filter_map(|w| app_data.workspace_state.workspace_info(w))
+                    .exactly_one()
+                    .ok()
+                    .map(|workspace| Inverse::ToplevelWorkspace {
+                        identifier: identifier.clone(),
+                        workspace: workspace.name.clone(),
+                        display: workspace_display(app_data, workspace),
+                    })
+                    .into_iter()
+                    .collect(),
+            }
+        }
+        Request::Workspace { handle, action, .. } => {
+            let Some(workspace) = app_data.workspace_state.workspace_info(handle) else {
+                return Vec::new();
+            };
+            let Some(group) = get_groups_for_workspace(workspace, app_data).next() else {
+                return Vec::new();
+            };
+            let display = workspace::group_display(app_data, group);
+            match action {
+                WorkspaceRequest::Activate => workspace::group_workspaces(app_data, group)
+                    .filter(|w| w.state.contains(WorkspaceState::Active))
+                    .filter(|w| w.handle != *handle)
+                    .map(|w| Inverse::WorkspaceActivate {
+                        name: w.name.clone(),
+                        display: display.clone(),
+                    })
+                    .collect(),
+                WorkspaceRequest::Pin { .. } => vec![Inverse::WorkspacePin {
+                    name: workspace.name.clone(),
+                    display,
+                    pin: workspace
+                        .cosmic_state
+                        .contains(CosmicWorkspaceState::Pinned),
+                }],
+                WorkspaceRequest::Move { other, .. } => {
+                    let Some(display) = display else {
+                        return Vec::new();
+                    };
+                    let mut inverses = vec![Inverse::WorkspaceOrder {
+                        display: display.clone(),
+                        names: workspace::group_workspaces(app_data, group)
+                            .map(|w| w.name.clone())
+                            .collect(),
+                    }];
+                    if !group.workspaces.contains(other) {
+                        inverses.push(Inverse::WorkspaceDisplay {
+                            name: workspace.name.clone(),
+                            from: app_data
+                                .workspace_state
+                                .workspace_info(other)
+                                .and_then(|other| workspace_display(app_data, other)),
+                            to: display,
+                        });
+                    }
+                    inverses
+                }
+            }
+        }
+        Request::WorkspaceCommit => Vec::new(),
+    }
+}
+
+fn workspace_display(app_data: &AppData, workspace: &Workspace) -> Option<String> {
+    get_groups_for_workspace(workspace, app_data)
+        .next()
+        .and_then(|group| workspace::group_display(app_data, group))
+}
+
+/// Add the inverses recorded in [AppData::inverses] to the journal.
+pub fn record(app_data: &AppData) -> Result<()> {
+    let Some(inverses) = app_data.inverses.borrow_mut().take() else {
+        return Ok(());
+    };
+    if inverses.is_empty() {
+        return Ok(());
+    }
+
+    let mut journal: Journal = state::load(STATE_NAME)?;
+    journal.entries.push(Entry {
+        description: app_data
+            .requests
+            .borrow()
+            .iter()
+            .filter(|r| !matches!(r, Request::WorkspaceCommit))
+            .join(", "),
+        inverses,
+    });
+    if journal.entries.len() > MAX_ENTRIES {
+        journal.entries.remove(0);
+    }
+    state::save(app_data, STATE_NAME, &journal)
+}
+
+/// Undo the last `count` commands, newest first.
+///
+/// Each change is applied before the next one, because restoring the order of
+/// workspaces depends on the workspaces being back on their display.
+pub fn run<W: Write>(
+    session: &mut Session,
+    count: usize,
+    timeout: Duration,
+    printer: &mut impl Print<W>,
+) -> Result<()> {
+    // undoing is not recorded itself
+    session.app_data.inverses.borrow_mut().take();
+    let dry_run = session.app_data.dry_run;
+
+    let mut journal: Journal = state::load(STATE_NAME)?;
+    if journal.entries.is_empty() {
+        bail!("nothing to undo");
+    }
+    if count > journal.entries.len() {
+        warn!("only {} commands can be undone", journal.entries.len());
+    }
+
+    let mut undone = Vec::new();
+    for _ in 0..count {
+        let Some(entry) = journal.entries.pop() else {
+            break;
+        };
+        // saved before anything is applied, so an entry is never undone twice
+        state::save(&session.app_data, STATE_NAME, &journal)?;
+        for inverse in entry.inverses.iter().rev() {
+            if !dry_run {
+                session.app_data.requests.borrow_mut().clear();
+            }
+            if let Err(err) = apply(&session.app_data, inverse) {
+                warn!("failed to undo {inverse:?}: {err:#}");
+                continue;
+            }
+            if !dry_run {
+                session.event_queue.flush()?;
+                if let Err(err) = verify::wait_until_applied(session, timeout) {
+                    warn!("failed to undo {inverse:?}: {err:#}");
+                }
+            }
+        }
+        undone.push(entry.description);
+    }
+
+    printer.list("Undone", undone.iter())?;
+    if dry_run {
+        let requests = session.app_data.requests.borrow();
+        printer.list("Requests", requests.iter())?;
+    }
+    Ok(())
+}
+
+fn apply(app_data: &AppData, inverse: &Inverse) -> Result<()> {
+    match inverse {
+        Inverse::ToplevelFlag {
+            identifier,
+            flag,
+            set,
+        } => {
+            let toplevel = toplevel::find_from_id(app_data, identifier)?;
+            let action = if *set {
+                SetStateAction::Set
+            } else {
+                SetStateAction::Unset
+            };
+            match flag {
+                ToplevelFlag::Maximized => toplevel::maximize(app_data, toplevel, action),
+                ToplevelFlag::Minimized => toplevel::minimize(app_data, toplevel, action),
+                ToplevelFlag::Fullscreen => toplevel::fullscreen(app_data, toplevel, action),
+                ToplevelFlag::Sticky => toplevel::sticky(app_data, toplevel, action),
+            }
+        }
+        Inverse::ToplevelWorkspace {
+            identifier,
+            workspace,
+            display,
+        } => toplevel::move_to(
+            app_data,
+            toplevel::find_from_id(app_data, identifier)?,
+            &WorkspaceIdent {
+                name: workspace.clone(),
+                display: display.clone(),
+            },
+        ),
+        Inverse::ToplevelActivate { identifier } => {
+            toplevel::activate(app_data, toplevel::find_from_id(app_data, identifier)?)
+        }
+        Inverse::WorkspacePin { name, display, pin } => workspace::pin(
+            app_data,
+            WorkspaceIdent {
+                name: name.clone(),
+                display: display.clone(),
+            },
+            *pin,
+        ),
+        Inverse::WorkspaceActivate { name, display } => workspace::activate(
+            app_data,
+            WorkspaceIdent {
+                name: name.clone(),
+                display: display.clone(),
+            },
+        ),
+        Inverse::WorkspaceDisplay { name, from, to } => workspace::move_to(
+            app_data,
+            WorkspaceIdent {
+                name: name.clone(),
+                display: from.clone(),
+            },
+            usize::MAX,
+            Some(to),
+        ),
+        Inverse::WorkspaceOrder { display, names } => {
+            // workspaces that were removed since can't be restored
+            let names: Vec<_> = names
+                .iter()
+                .filter(|name| {
+                    let workspace = WorkspaceIdent {
+                        name: name.to_string(),
+                        display: Some(display.clone()),
+                    };
+                    get_workspace(app_data, &workspace).is_ok()
+                })
+                .cloned()
+                .collect();
+            workspace::reorder(app_data, Some(display), &names)
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    io::Write,
+    mem::discriminant,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, bail};
+use cosmic_protocols::{
+    toplevel_info::v1::client::zcosmic_toplevel_handle_v1::State,
+    workspace::v2::client::zcosmic_workspace_handle_v2::State as CosmicWorkspaceState,
+};
+use itertools::Itertools;
+use log::debug;
+use wayland_protocols::ext::workspace::v1::client::ext_workspace_handle_v1::{
+    ExtWorkspaceHandleV1, State as WorkspaceState,
+};
+
+use crate::{
+    cosmic::{AppData, Session},
+    error::Error,
+    print::{Print, PrintList},
+    request::{Request, ToplevelRequest, WorkspaceRequest},
+    snapshot::{self, Snapshot},
+    toplevel, workspace,
+};
+
+/// Roundtrip until the compositor applied all requests sent so far or
+/// `timeout` is reached.
+///
+/// Only the last request per target and kind of change is checked, e.g.
+/// a workspace that is moved twice only has to end up at the second position.
+/// Moved workspaces are compared against the order all moves together result
+/// in, because a later move can separate a workspace from the one it was
+/// moved next to.
+pub fn wait_until_applied(session: &mut Session, timeout: Duration) -> Result<()> {
+    let requests = session.app_data.requests.borrow().clone();
+    let expected = last_per_target(&requests);
+    if expected.is_empty() {
+        debug!("nothing to verify, no requests were sent");
+        return Ok(());
+    }
+    // the compositor did not send the new order yet, so this starts from the old one
+    let orders = expected_orders(&session.app_data, &requests);
+
+    let start = Instant::now();
+    loop {
+        session.event_queue.roundtrip(&mut session.app_data)?;
+
+        let pending: Vec<_> = expected
+            .iter()
+            .filter(|r| !is_applied(r, &session.app_data, &orders))
+            .collect();
+        if pending.is_empty() {
+            debug!("requests applied after {:?}", start.elapsed());
+            return Ok(());
+        }
+        if start.elapsed() >= timeout {
+            bail!(Error::Timeout(format!(
+                "The compositor did not apply the following changes within {timeout:?}: {}",
+                pending.iter().join(", ")
+            )));
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// Print the current state of all toplevels and workspaces targeted by
+/// the requests sent so far.
+pub fn print_state<W: Write>(app_data: &AppData, printer: &mut impl Print<W>) -> Result<()> {
+    let requests = app_data.requests.borrow();
+    let snapshot = Snapshot::new(app_data);
+
+    let toplevels: Vec<_> = requests
+        .iter()
+        .filter_map(|r| match r {
+            Request::Toplevel { identifier, .. } => Some(identifier),
+            _ => None,
+        })
+        .unique()
+        .filter_map(|id| snapshot.toplevels.iter().find(|t| &t.identifier == id))
+        .collect();
+    if !toplevels.is_empty() {
+        let mut printer = printer.sub_list("Toplevels")?;
+        for toplevel in toplevels {
+            toplevel::print_toplevel(&snapshot, &mut printer.sub_struct()?, toplevel, false)?;
+        }
+    }
+
+    let workspaces: Vec<_> = requests
+        .iter()
+        .filter_map(|r| match r {
+            Request::Workspace { handle, .. } => Some(handle),
+            _ => None,
+        })
+        .unique()
+        .filter_map(|handle| snapshot::workspace_index(app_data, handle))
+        .collect();
+    if !workspaces.is_empty() {
+        let mut printer = printer.sub_list("Workspaces")?;
+        for workspace in workspaces {
+            workspace::print_workspace(&snapshot, &mut printer.sub_struct()?, workspace, false)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn last_per_target(requests: &[Request]) -> Vec<Request> {
+    let mut last: Vec<Request> = Vec::new();
+    for request in requests.iter().rev() {
+        if matches!(request, Request::WorkspaceCommit) {
+            continue;
+        }
+        if !last.iter().any(|kept| same_target(kept, request)) {
+            last.push(request.clone());
+        }
+    }
+    last.reverse();
+    last
+}
+
+fn same_target(a: &Request, b: &Request) -> bool {
+    match (a, b) {
+        (
+            Request::Toplevel {
+                identifier: a_id,
+                action: a_action,
+                ..
+            },
+            Request::Toplevel {
+                identifier: b_id,
+                action: b_action,
+                ..
+            },
+        ) => a_id == b_id && discriminant(a_action) == discriminant(b_action),
+        (
+            Request::Workspace {
+                handle: a_handle,
+                action: a_action,
+                ..
+            },
+            Request::Workspace {
+                handle: b_handle,
+                action: b_action,
+                ..
+            },
+        ) => a_handle == b_handle && discriminant(a_action) == discriminant(b_action),
+        _ => false,
+    }
+}
+
+/// The workspace order of every group once all moves in `requests` are applied.
+fn expected_orders(app_data: &AppData, requests: &[Request]) -> Vec<Vec<ExtWorkspaceHandleV1>> {
+    let moves: Vec<_> = requests
+        .iter()
+        .filter_map(|r| match r {
+            Request::Workspace {
+                handle,
+                action: WorkspaceRequest::Move { other, after, .. },
+                ..
+            } => Some((handle.clone(), other.clone(), *after)),
+            _ => None,
+        })
+        .collect();
+    let mut orders: Vec<_> = app_data
+        .workspace_state
+        .workspace_groups()
+        .map(|group| group.workspaces.clone())
+        .collect();
+    workspace::apply_moves(&mut orders, &moves);
+    orders
+}
+
+/// Whether `workspace` has the same neighbours in `actual` as in the
+/// expected order of the group it ends up in.
+fn is_in_place<T: PartialEq>(expected: &[Vec<T>], actual: &[T], workspace: &T) -> bool {
+    let Some(expected) = expected.iter().find(|order| order.contains(workspace)) else {
+        return false;
+    };
+    actual.contains(workspace)
+        && workspace::neighbours(expected, workspace) == workspace::neighbours(actual, workspace)
+}
+
+fn is_applied(request: &Request, app_data: &AppData, orders: &[Vec<ExtWorkspaceHandleV1>]) -> bool {
+    match request {
+        Request::Toplevel {
+            identifier, action, ..
+        } => {
+            let toplevel = app_data
+                .toplevel_info_state
+                .toplevels()
+                .find(|t| &t.identifier == identifier);
+            let Some(toplevel) = toplevel else {
+                return matches!(action, ToplevelRequest::Close);
+            };
+            match action {
+                ToplevelRequest::Maximize(set) => {
+                    toplevel.state.contains(&State::Maximized) == *set
+                }
+                ToplevelRequest::Minimize(set) => {
+                    toplevel.state.contains(&State::Minimized) == *set
+                }
+                ToplevelRequest::Fullscreen(set) => {
+                    toplevel.state.contains(&State::Fullscreen) == *set
+                }
+                ToplevelRequest::Sticky(set) => toplevel.state.contains(&State::Sticky) == *set,
+                ToplevelRequest::Activate(_) => toplevel.state.contains(&State::Activated),
+                ToplevelRequest::Close => false,
+                ToplevelRequest::MoveToWorkspace { workspace, .. } => {
+                    toplevel.workspace.contains(workspace)
+                }
+            }
+        }
+        Request::Workspace { handle, action, .. } => {
+            let Some(workspace) = app_data.workspace_state.workspace_info(handle) else {
+                return false;
+            };
+            match action {
+                WorkspaceRequest::Activate => workspace.state.contains(WorkspaceState::Active),
+                WorkspaceRequest::Pin { pin, .. } => {
+                    workspace
+                        .cosmic_state
+                        .contains(CosmicWorkspaceState::Pinned)
+                        == *pin
+                }
+                WorkspaceRequest::Move { .. } => {
+                    let Some(group) = workspace::get_groups_for_workspace(workspace, app_data)
+                        .exactly_one()
+                        .ok()
+                    else {
+                        return false;
+                    };
+                    is_in_place(orders, &group.workspaces, handle)
+                }
+            }
+        }
+        Request::WorkspaceCommit => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_are_checked_against_the_final_order() {
+        // swapping 1 and 3 moves 1 before 3 and then 3 before 2, so 1 does
+        // not end up next to 3
+        let expected = [vec![3, 2, 1, 4]];
+        assert!(is_in_place(&expected, &[3, 2, 1, 4], &1));
+        assert!(is_in_place(&expected, &[3, 2, 1, 4], &3));
+        // only the first move was applied yet
+        assert!(!is_in_place(&expected, &[2, 1, 3, 4], &1));
+        assert!(!is_in_place(&expected, &[2, 1, 3, 4], &3));
+    }
+
+    #[test]
+    fn moved_to_another_group() {
+        let expected = [vec![1, 3], vec![2]];
+        assert!(is_in_place(&expected, &[2], &2));
+        assert!(!is_in_place(&expected, &[1, 2], &2));
+        assert!(!is_in_place(&expected, &[4], &4));
+    }
+
+    #[test]
+    fn moved_to_the_ends() {
+        let expected = [vec![3, 1, 2]];
+        assert!(is_in_place(&expected, &[3, 1, 2], &3));
+        assert!(!is_in_place(&expected, &[1, 3, 2], &3));
+        let expected = [vec![2, 3, 1]];
+        assert!(is_in_place(&expected, &[2, 3, 1], &1));
+        assert!(!is_in_place(&expected, &[2, 1, 3], &1));
+    }
+}
@@ -1,133 +1,99 @@
-use std::io::Write;
+use std::{cmp::Reverse, io::Write};
 
 use crate::{
-    args::WorkspaceIdent,
+    args::{SortKey, WorkspaceIdent},
     cosmic::AppData,
+    error::Error,
+    marks,
     output::{self, print_displays},
     print::{ListOptions, Print, PrintList},
+    request::{Request, WorkspaceRequest},
+    snapshot::Snapshot,
 };
 
-use anyhow::{Context, Result, bail};
+use anyhow::{Result, bail};
 use cosmic_client_toolkit::{
     toplevel_info::ToplevelInfo,
     workspace::{Workspace, WorkspaceGroup},
 };
-use cosmic_protocols::workspace::v2::client::zcosmic_workspace_handle_v2::{
-    TilingState, WorkspaceCapabilities,
-};
+use cosmic_protocols::workspace::v2::client::zcosmic_workspace_handle_v2::TilingState;
+use itertools::Itertools;
 use log::warn;
 use wayland_client::Proxy;
-use wayland_protocols::ext::workspace::v1::client::ext_workspace_handle_v1::WorkspaceCapabilities as ExtWorkspaceCapabilities;
-use wayland_protocols::ext::workspace::v1::client::{
-    ext_workspace_group_handle_v1::GroupCapabilities, ext_workspace_handle_v1,
-};
+use wayland_protocols::ext::workspace::v1::client::ext_workspace_handle_v1::ExtWorkspaceHandleV1;
 
-pub fn list_groups<W: Write>(app_data: &AppData, printer: &mut impl Print<W>) -> Result<()> {
+pub fn list_groups<W: Write>(snapshot: &Snapshot, printer: &mut impl Print<W>) -> Result<()> {
     let mut printer = printer.sub_list("Workspace Groups")?;
-    for wg in app_data.workspace_state.workspace_groups() {
+    for wg in snapshot.groups.iter() {
         let mut printer = printer.sub_struct()?;
-        print_displays(app_data, &mut printer, &wg.outputs)?;
+        print_displays(&mut printer, snapshot.displays(&wg.outputs))?;
         printer.field("workspace count", wg.workspaces.len())?;
-        printer.field(
-            "can create workspace",
-            wg.capabilities.contains(GroupCapabilities::CreateWorkspace),
-        )?;
+        printer.field("can create workspace", wg.can_create_workspace)?;
     }
 
     Ok(())
 }
 
 pub fn list<W: Write>(
-    app_data: &AppData,
+    snapshot: &Snapshot,
     printer: &mut impl Print<W>,
     print_capabilities: bool,
 ) -> Result<()> {
     let mut printer = printer.sub_list("Workspaces")?;
-    for workspace in app_data.workspace_state.workspaces() {
-        let mut printer = printer.sub_struct()?;
-        printer.field("Name", &workspace.name)?;
-        printer.optional("wayland id", workspace.id.as_ref())?;
-        let displays =
-            get_groups_for_workspace(workspace, app_data).flat_map(|wg| wg.outputs.iter());
-        print_displays(app_data, &mut printer, displays)?;
-        printer.field("Tiling", is_workspace_tiling(workspace))?;
-        printer.field(
-            "Toplevel count",
-            workspace_toplevels(workspace, app_data).count(),
+    for index in 0..snapshot.workspaces.len() {
+        print_workspace(
+            snapshot,
+            &mut printer.sub_struct()?,
+            index,
+            print_capabilities,
         )?;
-        {
-            use ext_workspace_handle_v1::State;
-            let mut printer = printer.sub_list_with("State", ListOptions { inline: true })?;
-            if workspace.state.contains(State::Active) {
-                printer.item("active")?;
-            }
-            if workspace.state.contains(State::Hidden) {
-                printer.item("hidden")?;
-            }
-            if workspace.state.contains(State::Urgent) {
-                printer.item("urgent")?;
-            }
+    }
+    Ok(())
+}
+
+/// Print the workspace at `index` in [Snapshot::workspaces].
+pub fn print_workspace<W: Write>(
+    snapshot: &Snapshot,
+    printer: &mut impl Print<W>,
+    index: usize,
+    print_capabilities: bool,
+) -> Result<()> {
+    let workspace = &snapshot.workspaces[index];
+    printer.field("Name", &workspace.name)?;
+    printer.optional("wayland id", workspace.wayland_id.as_ref())?;
+    print_displays(printer, snapshot.workspace_displays(workspace))?;
+    printer.field("Tiling", workspace.tiling)?;
+    printer.field("Toplevel count", snapshot.toplevel_count(index))?;
+    {
+        let mut printer = printer.sub_list_with("State", ListOptions { inline: true })?;
+        if workspace.active {
+            printer.item("active")?;
         }
-        if print_capabilities {
-            let mut printer =
-                printer.sub_list_with("Capabilities", ListOptions { inline: true })?;
-            if workspace
-                .cosmic_capabilities
-                .contains(WorkspaceCapabilities::Move)
-            {
-                printer.item("move")?;
-            }
-            if workspace
-                .cosmic_capabilities
-                .contains(WorkspaceCapabilities::Pin)
-            {
-                printer.item("pin")?;
-            }
-            if workspace
-                .cosmic_capabilities
-                .contains(WorkspaceCapabilities::Rename)
-            {
-                printer.item("rename")?;
-            }
-            if workspace
-                .cosmic_capabilities
-                .contains(WorkspaceCapabilities::Pin)
-            {
-                printer.item("set tiling")?;
-            }
-            if workspace
-                .capabilities
-                .contains(ExtWorkspaceCapabilities::Activate)
-            {
-                printer.item("activate")?;
-            }
-            if workspace
-                .capabilities
-                .contains(ExtWorkspaceCapabilities::Assign)
-            {
-                printer.item("assign")?;
-            }
-            if workspace
-                .capabilities
-                .contains(ExtWorkspaceCapabilities::Deactivate)
-            {
-                printer.item("deactivate")?;
-            }
-            if workspace
-                .capabilities
-                .contains(ExtWorkspaceCapabilities::Remove)
-            {
-                printer.item("remove")?;
-            }
+        if workspace.hidden {
+            printer.item("hidden")?;
         }
+        if workspace.urgent {
+            printer.item("urgent")?;
+        }
+    }
+    if print_capabilities {
+        printer.list_with(
+            "Capabilities",
+            workspace.capabilities.iter(),
+            ListOptions { inline: true },
+        )?;
     }
     Ok(())
 }
 
+/// Find the workspace by name or by a mark like "@chat".
 pub fn get_workspace<'a>(
     app_data: &'a AppData,
     workspace: &WorkspaceIdent,
 ) -> Result<(&'a WorkspaceGroup, usize, &'a Workspace)> {
+    if let Some(mark) = workspace.name.strip_prefix(marks::PREFIX) {
+        return get_workspace(app_data, &marks::workspace(mark)?);
+    }
     if let Some(display) = workspace.display.as_ref() {
         let Some(group) = app_data.workspace_state.workspace_groups().find(|group| {
             group
@@ -136,7 +102,7 @@ pub fn get_workspace<'a>(
                 .filter_map(|o| app_data.output_state.info(o))
                 .any(|o| &output::display_name(&o) == display)
         }) else {
-            bail!("Unknonw display: {}", display);
+            bail!(Error::NotFound(format!("Unknown display: {display}")));
         };
 
         let Some((workspace_pos, workspace)) = group
@@ -151,10 +117,10 @@ pub fn get_workspace<'a>(
             })
             .find(|(_, w)| w.name == workspace.name)
         else {
-            bail!(
+            bail!(Error::NotFound(format!(
                 "Workspace {} does not exist on display {display}",
                 workspace.name
-            );
+            )));
         };
 
         Ok((group, workspace_pos, workspace))
@@ -165,14 +131,17 @@ pub fn get_workspace<'a>(
             .filter(|w| w.name == workspace.name);
 
         let Some(workspace) = candidate_workspaces.next() else {
-            bail!("Workspace {} does not exist", workspace.name);
+            bail!(Error::NotFound(format!(
+                "Workspace {} does not exist",
+                workspace.name
+            )));
         };
 
         if candidate_workspaces.next().is_some() {
-            bail!(
+            bail!(Error::Ambiguous(format!(
                 "Found multiple workspaces with name {}. Specify display to narrow down selection",
                 workspace.name
-            );
+            )));
         }
 
         let Some((workspace_pos, group)) = app_data
@@ -181,10 +150,10 @@ pub fn get_workspace<'a>(
             .enumerate()
             .find(|(_, group)| group.workspaces.contains(&workspace.handle))
         else {
-            bail!(
+            bail!(Error::Internal(format!(
                 "Found workspace {} but could not access it's group",
                 workspace.name
-            );
+            )));
         };
 
         Ok((group, workspace_pos, workspace))
@@ -192,42 +161,32 @@ pub fn get_workspace<'a>(
 }
 
 pub fn pin(app_data: &AppData, workspace: WorkspaceIdent, pin: bool) -> Result<()> {
-    let workspace_manager = app_data
-        .workspace_state
-        .workspace_manager()
-        .get()
-        .context("could not get acccess to workspace manager")?;
-
     let (_, _, workspace) = get_workspace(app_data, &workspace)?;
 
     let Some(cosmic_handle) = workspace.cosmic_handle.as_ref() else {
-        bail!(
+        bail!(Error::Internal(format!(
             "INTERNAL: No cosmic handle for workspace {}",
             workspace.name
-        );
+        )));
     };
 
-    if pin {
-        cosmic_handle.pin();
-    } else {
-        cosmic_handle.unpin();
-    }
-    workspace_manager.commit();
+    app_data.send(Request::workspace(
+        workspace,
+        WorkspaceRequest::Pin {
+            cosmic_handle: cosmic_handle.clone(),
+            pin,
+        },
+    ))?;
+    app_data.send(Request::WorkspaceCommit)?;
 
     Ok(())
 }
 
 pub fn activate(app_data: &AppData, workspace: WorkspaceIdent) -> Result<()> {
-    let workspace_manager = app_data
-        .workspace_state
-        .workspace_manager()
-        .get()
-        .context("could not get acccess to workspace manager")?;
-
     let (_, _, workspace) = get_workspace(app_data, &workspace)?;
 
-    workspace.handle.activate();
-    workspace_manager.commit();
+    app_data.send(Request::workspace(workspace, WorkspaceRequest::Activate))?;
+    app_data.send(Request::WorkspaceCommit)?;
 
     Ok(())
 }
@@ -244,25 +203,10 @@ pub fn move_to(
     target_position: usize,
     target_display: Option<&str>,
 ) -> Result<()> {
-    let workspace_manager = app_data
-        .workspace_state
-        .workspace_manager()
-        .get()
-        .context("could not get acccess to workspace manager")?;
-
     let (orig_group, current_pos, workspace) = get_workspace(app_data, &workspace)?;
 
     let group = if let Some(target_display) = target_display {
-        let Some(group) = app_data.workspace_state.workspace_groups().find(|group| {
-            group
-                .outputs
-                .iter()
-                .filter_map(|o| app_data.output_state.info(o))
-                .any(|o| output::display_name(&o) == target_display)
-        }) else {
-            bail!("Unknonw display: {}", target_display);
-        };
-        group
+        group_for_display(app_data, target_display)?
     } else {
         orig_group
     };
@@ -301,27 +245,348 @@ pub fn move_to(
         unless position is 0 in which case 1 is valid, because current_pos > 0",
     );
 
-    // aparently every value but 0 is ignored. Not sure what this means, but the current
-    // cosmic-compositor just checks that it is 0. Events with other values are ignored.
-    const AXIS: u32 = 0;
+    move_next_to(app_data, workspace, other_workspace, move_after)?;
+    app_data.send(Request::WorkspaceCommit)?;
 
-    let Some(cosmic_handle) = workspace.cosmic_handle.as_ref() else {
+    Ok(())
+}
+
+/// Move the workspaces of a group into the order of `names`, followed by the
+/// workspaces that are not listed.
+///
+/// Without `display` the group is the one that has the workspaces.
+pub fn reorder(app_data: &AppData, display: Option<&str>, names: &[String]) -> Result<()> {
+    let group = match display {
+        Some(display) => group_for_display(app_data, display)?,
+        None => {
+            let mut groups = app_data
+                .workspace_state
+                .workspace_groups()
+                .filter(|group| group_workspaces(app_data, group).any(|w| names.contains(&w.name)));
+            match (groups.next(), groups.next()) {
+                (Some(group), None) => group,
+                (Some(_), Some(_)) => bail!(Error::Ambiguous(
+                    "the workspaces are on multiple displays, specify the display".to_string()
+                )),
+                (None, _) => bail!(Error::NotFound(format!(
+                    "Workspace {} does not exist",
+                    names[0]
+                ))),
+            }
+        }
+    };
+
+    let mut target = Vec::new();
+    for name in names {
+        let Some(workspace) = group_workspaces(app_data, group).find(|w| &w.name == name) else {
+            bail!(Error::NotFound(format!(
+                "Workspace {name} is not on the display"
+            )));
+        };
+        if target
+            .iter()
+            .any(|w: &&Workspace| w.handle == workspace.handle)
+        {
+            bail!("Workspace {name} is listed twice");
+        }
+        target.push(workspace);
+    }
+    let rest: Vec<_> = group_workspaces(app_data, group)
+        .filter(|w| !target.iter().any(|t| t.handle == w.handle))
+        .collect();
+    target.extend(rest);
+
+    if apply_order(app_data, group, &target)? {
+        app_data.send(Request::WorkspaceCommit)?;
+    }
+    Ok(())
+}
+
+/// Sort the workspaces of every group or only of the group on `display`.
+pub fn sort(app_data: &AppData, display: Option<&str>, by: SortKey) -> Result<()> {
+    let groups: Vec<_> = match display {
+        Some(display) => vec![group_for_display(app_data, display)?],
+        None => app_data.workspace_state.workspace_groups().collect(),
+    };
+
+    let mut moved = false;
+    for group in groups {
+        let mut target: Vec<_> = group_workspaces(app_data, group).collect();
+        match by {
+            // numbers first and in numeric order, so "10" comes after "9"
+            SortKey::Name => {
+                target.sort_by_key(|w| w.name.parse::<u64>().map_err(|_| w.name.clone()))
+            }
+            SortKey::ToplevelCount => {
+                target.sort_by_key(|w| Reverse(workspace_toplevels(w, app_data).count()))
+            }
+        }
+        moved |= apply_order(app_data, group, &target)?;
+    }
+
+    if moved {
+        app_data.send(Request::WorkspaceCommit)?;
+    }
+    Ok(())
+}
+
+/// Send the moves that put the workspaces of `group` into the order of `target`.
+///
+/// Returns whether anything had to be moved. The moves still need to be committed.
+fn apply_order(app_data: &AppData, group: &WorkspaceGroup, target: &[&Workspace]) -> Result<bool> {
+    let target: Vec<_> = target.iter().map(|w| w.handle.clone()).collect();
+    let moves = plan_moves(&group.workspaces, &target);
+    send_moves(app_data, &moves)?;
+    Ok(!moves.is_empty())
+}
+
+/// The moves that turn the order `current` into `target`, as
+/// `(workspace, other, after)` to move `workspace` right after or before `other`.
+///
+/// The longest run of workspaces that are already in the right relative order
+/// stays in place and every other workspace is moved once, which is the least
+/// number of moves possible. `target` has to contain the same workspaces as `current`.
+fn plan_moves<T: PartialEq + Clone>(current: &[T], target: &[T]) -> Vec<(T, T, bool)> {
+    let positions: Vec<usize> = current
+        .iter()
+        .filter_map(|c| target.iter().position(|t| t == c))
+        .collect();
+    let stable = longest_increasing(&positions);
+
+    let mut order = current.to_vec();
+    let mut moves = Vec::new();
+    for (i, workspace) in target.iter().enumerate() {
+        if stable.contains(&i) {
+            continue;
+        }
+        // everything before i is already in order, so i goes right after i - 1
+        let (other, after) = match i.checked_sub(1) {
+            Some(prev) => (target[prev].clone(), true),
+            None => (order[0].clone(), false),
+        };
+        if other == *workspace {
+            continue;
+        }
+        order.retain(|w| w != workspace);
+        let pos = order
+            .iter()
+            .position(|w| *w == other)
+            .expect("other is in the order, because target has the same workspaces as current");
+        order.insert(if after { pos + 1 } else { pos }, workspace.clone());
+        moves.push((workspace.clone(), other, after));
+    }
+    moves
+}
+
+/// One longest strictly increasing subsequence of `values`.
+///
+/// Quadratic, but groups only have a handful of workspaces.
+fn longest_increasing(values: &[usize]) -> Vec<usize> {
+    // length and predecessor of the longest subsequence ending at each index
+    let mut len = vec![1; values.len()];
+    let mut prev = vec![None; values.len()];
+    for i in 0..values.len() {
+        for j in 0..i {
+            if values[j] < values[i] && len[j] + 1 > len[i] {
+                len[i] = len[j] + 1;
+                prev[i] = Some(j);
+            }
+        }
+    }
+
+    let Some(mut i) = (0..values.len()).max_by_key(|i| len[*i]) else {
+        return Vec::new();
+    };
+    let mut result = vec![values[i]];
+    while let Some(p) = prev[i] {
+        result.push(values[p]);
+        i = p;
+    }
+    result
+}
+
+fn group_for_display<'a>(app_data: &'a AppData, display: &str) -> Result<&'a WorkspaceGroup> {
+    let Some(group) = app_data.workspace_state.workspace_groups().find(|group| {
+        group
+            .outputs
+            .iter()
+            .filter_map(|o| app_data.output_state.info(o))
+            .any(|o| output::display_name(&o) == display)
+    }) else {
+        bail!(Error::NotFound(format!("Unknown display: {display}")));
+    };
+    Ok(group)
+}
+
+/// The name of the display of `group`, if it is on exactly one display.
+pub fn group_display(app_data: &AppData, group: &WorkspaceGroup) -> Option<String> {
+    group
+        .outputs
+        .iter()
+        .exactly_one()
+        .ok()
+        .and_then(|o| app_data.output_state.info(o))
+        .map(|o| output::display_name(&o))
+}
+
+/// The workspaces of `group` in order.
+pub fn group_workspaces<'a>(
+    app_data: &'a AppData,
+    group: &'a WorkspaceGroup,
+) -> impl Iterator<Item = &'a Workspace> {
+    group
+        .workspaces
+        .iter()
+        .filter_map(|handle| app_data.workspace_state.workspace_info(handle))
+}
+
+/// Exchange the positions of two workspaces, which may be on different displays.
+pub fn swap(app_data: &AppData, first: WorkspaceIdent, second: WorkspaceIdent) -> Result<()> {
+    let (first_group, _, first) = get_workspace(app_data, &first)?;
+    let (second_group, _, second) = get_workspace(app_data, &second)?;
+    if first.handle == second.handle {
+        bail!("can not swap workspace {} with itself", first.name);
+    }
+
+    let Some(moves) = plan_swap(
+        &first_group.workspaces,
+        &first.handle,
+        &second_group.workspaces,
+        &second.handle,
+    ) else {
         bail!(
-            "INTERNAL: No cosmic handle for workspace {}",
-            workspace.name
+            "can not swap workspaces {} and {} because both are the only workspace on their display",
+            first.name,
+            second.name
         );
     };
+    send_moves(app_data, &moves)?;
+    app_data.send(Request::WorkspaceCommit)?;
 
-    if move_after {
-        cosmic_handle.move_after(other_workspace, AXIS);
-    } else {
-        cosmic_handle.move_before(other_workspace, AXIS);
+    Ok(())
+}
+
+/// The moves that exchange `first` in the order `first_group` with `second`
+/// in `second_group`, as `(workspace, other, after)` like [plan_moves].
+///
+/// Both can be in the same group. `None` if neither workspace has a
+/// neighbour to move next to.
+fn plan_swap<T: PartialEq + Clone>(
+    first_group: &[T],
+    first: &T,
+    second_group: &[T],
+    second: &T,
+) -> Option<Vec<(T, T, bool)>> {
+    let (first_before, first_after) = neighbours(first_group, first);
+    let (second_before, second_after) = neighbours(second_group, second);
+
+    // next to each other, a single move is enough
+    if first_after == Some(second) {
+        return Some(vec![(first.clone(), second.clone(), true)]);
+    }
+    if first_before == Some(second) {
+        return Some(vec![(first.clone(), second.clone(), false)]);
     }
-    workspace_manager.commit();
 
+    // put one workspace next to the other and then the other one where
+    // the first one was, which needs a neighbour to move next to
+    let (moved, target, anchor, after) = match (first_after, first_before) {
+        (Some(after), _) => (first, second, after, false),
+        (None, Some(before)) => (first, second, before, true),
+        (None, None) => match (second_after, second_before) {
+            (Some(after), _) => (second, first, after, false),
+            (None, Some(before)) => (second, first, before, true),
+            (None, None) => return None,
+        },
+    };
+    Some(vec![
+        (moved.clone(), target.clone(), false),
+        (target.clone(), anchor.clone(), after),
+    ])
+}
+
+/// The workspaces right before and after `workspace` in `order`.
+pub fn neighbours<'a, T: PartialEq>(
+    order: &'a [T],
+    workspace: &T,
+) -> (Option<&'a T>, Option<&'a T>) {
+    let Some(pos) = order.iter().position(|w| w == workspace) else {
+        return (None, None);
+    };
+    let before = pos.checked_sub(1).and_then(|pos| order.get(pos));
+    (before, order.get(pos + 1))
+}
+
+/// Apply moves as planned by [plan_moves] to the workspace orders of all
+/// groups, the same way the compositor does once they are committed.
+///
+/// A move next to a workspace that is in none of the groups is skipped.
+pub fn apply_moves<T: PartialEq + Clone>(groups: &mut [Vec<T>], moves: &[(T, T, bool)]) {
+    for (workspace, other, after) in moves {
+        if workspace == other || !groups.iter().any(|group| group.contains(other)) {
+            continue;
+        }
+        for group in groups.iter_mut() {
+            group.retain(|w| w != workspace);
+        }
+        for group in groups.iter_mut() {
+            if let Some(pos) = group.iter().position(|w| w == other) {
+                group.insert(if *after { pos + 1 } else { pos }, workspace.clone());
+                break;
+            }
+        }
+    }
+}
+
+/// Send the planned moves, without committing them.
+fn send_moves(
+    app_data: &AppData,
+    moves: &[(ExtWorkspaceHandleV1, ExtWorkspaceHandleV1, bool)],
+) -> Result<()> {
+    for (workspace, other, after) in moves {
+        let Some(workspace) = app_data.workspace_state.workspace_info(workspace) else {
+            bail!(Error::Internal(format!(
+                "INTERNAL: No info for workspace {}",
+                workspace.id()
+            )));
+        };
+        move_next_to(app_data, workspace, other, *after)?;
+    }
     Ok(())
 }
 
+/// Send a request to move `workspace` right before or after `other`,
+/// without committing it.
+fn move_next_to(
+    app_data: &AppData,
+    workspace: &Workspace,
+    other: &ExtWorkspaceHandleV1,
+    after: bool,
+) -> Result<()> {
+    let Some(cosmic_handle) = workspace.cosmic_handle.as_ref() else {
+        bail!(Error::Internal(format!(
+            "INTERNAL: No cosmic handle for workspace {}",
+            workspace.name
+        )));
+    };
+
+    let other_name = app_data
+        .workspace_state
+        .workspace_info(other)
+        .map(|w| w.name.clone())
+        .unwrap_or_default();
+
+    app_data.send(Request::workspace(
+        workspace,
+        WorkspaceRequest::Move {
+            cosmic_handle: cosmic_handle.clone(),
+            other: other.clone(),
+            other_name,
+            after,
+        },
+    ))
+}
+
 pub fn is_workspace_tiling(workspace: &Workspace) -> bool {
     match workspace.tiling.and_then(|s| s.into_result().ok()) {
         Some(TilingState::TilingEnabled) => true,
@@ -348,3 +613,72 @@ pub fn workspace_toplevels<'a>(
         .toplevels()
         .filter(|t| t.workspace.contains(&workspace.handle))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn swapped(groups: &[&[u32]], first: u32, second: u32) -> Vec<Vec<u32>> {
+        let group_of = |w: u32| *groups.iter().find(|g| g.contains(&w)).unwrap();
+        let moves = plan_swap(group_of(first), &first, group_of(second), &second).unwrap();
+        let mut groups: Vec<_> = groups.iter().map(|g| g.to_vec()).collect();
+        apply_moves(&mut groups, &moves);
+        groups
+    }
+
+    fn reordered(current: &[u32], target: &[u32]) -> (Vec<u32>, usize) {
+        let moves = plan_moves(current, target);
+        let mut groups = vec![current.to_vec()];
+        apply_moves(&mut groups, &moves);
+        (groups.remove(0), moves.len())
+    }
+
+    #[test]
+    fn longest_increasing_subsequence() {
+        let mut result = longest_increasing(&[3, 0, 1, 4, 2]);
+        result.sort();
+        assert_eq!(result, [0, 1, 2]);
+        assert_eq!(longest_increasing(&[2, 1, 0]).len(), 1);
+        assert_eq!(longest_increasing(&[]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn plan_moves_reaches_the_target() {
+        assert_eq!(reordered(&[1, 2, 3], &[1, 2, 3]), (vec![1, 2, 3], 0));
+        assert_eq!(reordered(&[1, 2, 3], &[3, 1, 2]), (vec![3, 1, 2], 1));
+        assert_eq!(reordered(&[1, 2, 3], &[2, 3, 1]), (vec![2, 3, 1], 1));
+        assert_eq!(
+            reordered(&[1, 2, 3, 4], &[4, 3, 2, 1]),
+            (vec![4, 3, 2, 1], 3)
+        );
+        assert_eq!(
+            reordered(&[1, 2, 3, 4, 5], &[2, 1, 4, 3, 5]),
+            (vec![2, 1, 4, 3, 5], 2)
+        );
+    }
+
+    #[test]
+    fn swap_adjacent() {
+        assert_eq!(swapped(&[&[1, 2, 3]], 1, 2), [[2, 1, 3]]);
+        assert_eq!(swapped(&[&[1, 2, 3]], 3, 2), [[1, 3, 2]]);
+    }
+
+    #[test]
+    fn swap_non_adjacent() {
+        assert_eq!(swapped(&[&[1, 2, 3, 4]], 1, 3), [[3, 2, 1, 4]]);
+        assert_eq!(swapped(&[&[1, 2, 3, 4]], 4, 2), [[1, 4, 3, 2]]);
+        assert_eq!(swapped(&[&[1, 2, 3, 4, 5]], 1, 5), [[5, 2, 3, 4, 1]]);
+    }
+
+    #[test]
+    fn swap_across_groups() {
+        assert_eq!(swapped(&[&[1, 2], &[3, 4]], 1, 4), [[4, 2], [3, 1]]);
+        assert_eq!(swapped(&[&[1, 2], &[3]], 2, 3), [vec![1, 3], vec![2]]);
+        assert_eq!(swapped(&[&[1], &[2, 3]], 1, 3), [vec![3], vec![2, 1]]);
+    }
+
+    #[test]
+    fn swap_needs_a_neighbour() {
+        assert_eq!(plan_swap(&[1], &1, &[2], &2), None);
+    }
+}
@@ -1,8 +1,8 @@
 use std::path::PathBuf;
 
 use clap::{Parser, ValueEnum};
-use clap_complete::{Generator, shells};
-use clap_complete_nushell::Nushell;
+use clap_complete::env::{Bash, Elvish, EnvCompleter, Fish, Zsh};
+use conductor::shells::Nushell;
 
 #[derive(Parser, Debug, Clone)]
 #[command(version, about)]
@@ -33,24 +33,27 @@ pub enum ShellType {
     Nu,
 }
 
-impl Generator for ShellType {
-    fn file_name(&self, name: &str) -> String {
+impl ShellType {
+    pub fn file_name(&self, name: &str) -> String {
         match self {
-            ShellType::Bash => shells::Bash.file_name(name),
-            ShellType::Elviszh => shells::Elvish.file_name(name),
-            ShellType::Fish => shells::Fish.file_name(name),
-            ShellType::Zsh => shells::Zsh.file_name(name),
-            ShellType::Nu => Nushell.file_name(name),
+            ShellType::Bash => format!("{name}.bash"),
+            ShellType::Elviszh => format!("{name}.elv"),
+            ShellType::Fish => format!("{name}.fish"),
+            ShellType::Zsh => format!("_{name}"),
+            ShellType::Nu => format!("{name}.nu"),
         }
     }
 
-    fn generate(&self, cmd: &clap::Command, buf: &mut dyn std::io::Write) {
+    /// The completer for the dynamic completion engine.
+    ///
+    /// The generated scripts call back into conductor to get the completions.
+    pub fn completer(&self) -> &'static dyn EnvCompleter {
         match self {
-            ShellType::Bash => shells::Bash.generate(cmd, buf),
-            ShellType::Elviszh => shells::Elvish.generate(cmd, buf),
-            ShellType::Fish => shells::Fish.generate(cmd, buf),
-            ShellType::Zsh => shells::Zsh.generate(cmd, buf),
-            ShellType::Nu => Nushell.generate(cmd, buf),
+            ShellType::Bash => &Bash,
+            ShellType::Elviszh => &Elvish,
+            ShellType::Fish => &Fish,
+            ShellType::Zsh => &Zsh,
+            ShellType::Nu => &Nushell,
         }
     }
 }
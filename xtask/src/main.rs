@@ -1,6 +1,7 @@
 mod args;
 
 use std::{
+    fs::File,
     io::Result,
     path::{Path, PathBuf, absolute},
 };
@@ -55,8 +56,14 @@ fn generate_completions(out_dir: &Path, shell: Option<ShellType>) -> Result<()>
 
 fn generate_completion(out_dir: &Path, shell: ShellType) -> Result<()> {
     let name = "conductor";
-    let mut cmd = conductor::args::Arguments::command();
-    clap_complete::generate_to(shell, &mut cmd, name, out_dir)?;
+    let mut file = File::create(out_dir.join(shell.file_name(name)))?;
+    shell.completer().write_registration(
+        conductor::shells::COMPLETE_VAR,
+        name,
+        name,
+        name,
+        &mut file,
+    )?;
 
     Ok(())
 }